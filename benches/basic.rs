@@ -2,14 +2,20 @@
 extern crate criterion;
 
 use criterion::Criterion;
-use flit::BloomFilter;
+use flit::{BinaryFuseFilter, BlockedBloomFilter, BloomFilter};
 use rand::distributions::{Distribution, Standard};
 use rand::thread_rng;
 use std::hash::Hash;
 
 fn add_to_filter<T: Hash>(filter: &mut BloomFilter<T>, items: &[T]) {
     for item in items {
-        filter.add(&item);
+        filter.add(item);
+    }
+}
+
+fn add_to_blocked_filter<T: Hash>(filter: &mut BlockedBloomFilter<T>, items: &[T]) {
+    for item in items {
+        filter.add(item);
     }
 }
 
@@ -36,10 +42,68 @@ fn benchmark_add(c: &mut Criterion) {
     });
 }
 
+fn benchmark_blocked_add(c: &mut Criterion) {
+    c.bench_function("blocked add 10000", |b| {
+        let nums = get_random_nums(10000);
+        let mut filter = BlockedBloomFilter::new(0.01, 10000);
+
+        b.iter(|| add_to_blocked_filter(&mut filter, &nums));
+    });
+}
+
+/// Compares lookups in filters that are much larger than the CPU caches, where the number of
+/// cache misses per lookup dominates.
+fn benchmark_might_contain(c: &mut Criterion) {
+    const ITEMS: usize = 10_000_000;
+
+    let nums = get_random_nums(ITEMS);
+    let lookups = get_random_nums(10000);
+
+    let mut filter = BloomFilter::new(0.01, ITEMS);
+    add_to_filter(&mut filter, &nums);
+
+    let mut blocked_filter = BlockedBloomFilter::new(0.01, ITEMS);
+    add_to_blocked_filter(&mut blocked_filter, &nums);
+
+    let fuse_filter = BinaryFuseFilter::new(&nums);
+
+    c.bench_function("might_contain 10000", |b| {
+        b.iter(|| {
+            lookups
+                .iter()
+                .filter(|num| filter.might_contain(num))
+                .count()
+        });
+    });
+
+    c.bench_function("blocked might_contain 10000", |b| {
+        b.iter(|| {
+            lookups
+                .iter()
+                .filter(|num| blocked_filter.might_contain(num))
+                .count()
+        });
+    });
+
+    c.bench_function("binary fuse might_contain 10000", |b| {
+        b.iter(|| {
+            lookups
+                .iter()
+                .filter(|num| fuse_filter.might_contain(num))
+                .count()
+        });
+    });
+}
+
 fn get_random_nums(n: usize) -> Vec<u32> {
     let mut rng = thread_rng();
     Standard.sample_iter(&mut rng).take(n).collect()
 }
 
-criterion_group!(benches, benchmark_add);
+criterion_group!(
+    benches,
+    benchmark_add,
+    benchmark_blocked_add,
+    benchmark_might_contain
+);
 criterion_main!(benches);
@@ -0,0 +1,340 @@
+//! `AtomicBloomFilter` is a Bloom filter that can be shared between threads, and added to
+//! concurrently without a lock.
+//!
+//! The bits of the filter are stored in `AtomicU64` words. Adding an item sets each of its bits
+//! with an atomic `fetch_or`, so concurrent inserts never overwrite each other's bits, and
+//! queries only need relaxed loads. Other than that, the filter behaves exactly like a
+//! [`BloomFilter`] with the same parameters and hasher, and sets the same bits for the same
+//! items.
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+
+use crate::bloom_filter::{
+    estimate_len, false_positive_rate, indices_for_hash, optimal_parameters, split_hash,
+};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Represents a Bloom filter that can be added to concurrently.
+///
+/// When constructing the filter using `new`, you need to specify the desired acceptable
+/// false-positive rate, and the number of items you intend to store in the filter.
+///
+/// All of the methods of the filter take `&self`, so the filter can be shared between threads
+/// using a plain reference or an `Arc`.
+///
+/// # Example
+/// ```rust
+/// use flit::AtomicBloomFilter;
+/// use std::thread;
+///
+/// let filter = AtomicBloomFilter::new(0.01, 10000);
+///
+/// thread::scope(|scope| {
+///     scope.spawn(|| filter.insert(&"Hello, world!"));
+///     scope.spawn(|| filter.insert(&"Dogs are cool!"));
+/// });
+///
+/// assert_eq!(filter.might_contain(&"Hello, world!"), true); // probably true
+/// assert_eq!(filter.might_contain(&"Dogs are cool!"), true); // probably true
+/// assert_eq!(filter.might_contain(&"Cats are cool!"), false); // definitely false!
+/// ```
+pub struct AtomicBloomFilter<T, S = XxHashBuilder> {
+    n: AtomicU64,
+    m: u64,
+    k: u32,
+    words: Vec<AtomicU64>,
+    build_hasher: S,
+    // The filter only ever borrows items, so it can be shared between threads even if `T` itself
+    // cannot.
+    _phantom: PhantomData<fn(&T)>,
+}
+
+impl<T: Hash> AtomicBloomFilter<T> {
+    /// Creates a new concurrent Bloom filter based on the required false positive rate and the
+    /// estimated number of items that will be added to the filter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_new` to handle these cases without panicking.
+    pub fn new(false_positive_rate: f64, estimated_items: usize) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new concurrent Bloom filter like `new`, but hashes items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_seed` to handle these cases without panicking.
+    pub fn with_seed(false_positive_rate: f64, estimated_items: usize, seed: u64) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Creates a new concurrent Bloom filter like `new`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_new(false_positive_rate: f64, estimated_items: usize) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new concurrent Bloom filter like `with_seed`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_seed(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        seed: u64,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> AtomicBloomFilter<T, S> {
+    /// Creates a new concurrent Bloom filter like `new`, but hashes items using the given
+    /// `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_hasher` to handle these cases without panicking.
+    pub fn with_hasher(false_positive_rate: f64, estimated_items: usize, build_hasher: S) -> Self {
+        Self::try_with_hasher(false_positive_rate, estimated_items, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new concurrent Bloom filter like `with_hasher`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_hasher(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        let (num_bits, num_hashes) = optimal_parameters(false_positive_rate, estimated_items)?;
+        let num_words = (num_bits as usize).div_ceil(64);
+
+        Ok(AtomicBloomFilter {
+            n: AtomicU64::new(0),
+            m: num_bits,
+            k: num_hashes,
+            words: (0..num_words).map(|_| AtomicU64::new(0)).collect(),
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Adds the `item` to the filter by atomically setting the appropriate bits in the filter to
+    /// `true`.
+    pub fn insert(&self, item: &T) {
+        for i in indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k) {
+            self.words[i / 64].fetch_or(1 << (i % 64), Ordering::Relaxed);
+        }
+
+        self.n.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely did not contain the item when its
+    /// bits were read. An item that is being added concurrently might not be found yet.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k)
+            .all(|i| self.words[i / 64].load(Ordering::Relaxed) & (1 << (i % 64)) != 0)
+    }
+
+    /// Calculates the current expected false positive rate given the number of items in the
+    /// filter.
+    pub fn false_positive_rate(&self) -> f64 {
+        false_positive_rate(self.len(), self.m, self.k)
+    }
+}
+
+impl<T, S> AtomicBloomFilter<T, S> {
+    /// Returns the number of items that have been added to the filter.
+    ///
+    /// Every call to `insert` is counted, even if the same item is added more than once.
+    pub fn len(&self) -> u64 {
+        self.n.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if no items have been added to the filter.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of bits in the filter (`m`).
+    pub fn num_bits(&self) -> u64 {
+        self.m
+    }
+
+    /// Returns the number of hashes applied to each item (`k`).
+    pub fn num_hashes(&self) -> u32 {
+        self.k
+    }
+
+    /// Estimates the number of distinct items in the filter from the number of bits that are set.
+    ///
+    /// Returns infinity if every bit in the filter is set.
+    pub fn estimated_len(&self) -> f64 {
+        estimate_len(self.count_ones(), self.m, self.k)
+    }
+
+    /// Returns the fraction of bits in the filter that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.m as f64
+    }
+
+    fn count_ones(&self) -> u64 {
+        self.words
+            .iter()
+            .map(|word| u64::from(word.load(Ordering::Relaxed).count_ones()))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BloomFilter;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_send_and_sync_for_any_item() {
+        fn assert_send_sync<F: Send + Sync>() {}
+
+        assert_send_sync::<AtomicBloomFilter<std::rc::Rc<u32>>>();
+    }
+
+    #[test]
+    fn test_no_false_negatives() {
+        let filter = AtomicBloomFilter::new(0.01_f64, 1000);
+
+        for i in 0..1000 {
+            filter.insert(&i);
+        }
+
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000).all(|i| filter.might_contain(&i)));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let filter = AtomicBloomFilter::with_seed(0.01_f64, 10_000, 42);
+
+        for i in 0..10_000 {
+            filter.insert(&i);
+        }
+
+        let false_positives = (10_000..110_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+
+        assert!(false_positives < 1100);
+    }
+
+    #[test]
+    fn test_concurrent_inserts_lose_no_bits() {
+        const THREADS: u64 = 8;
+        const ITEMS_PER_THREAD: u64 = 20_000;
+
+        // A small filter, so that threads frequently set bits in the same words.
+        let filter = AtomicBloomFilter::with_seed(0.1_f64, 10_000, 42);
+        let sequential = AtomicBloomFilter::with_seed(0.1_f64, 10_000, 42);
+
+        thread::scope(|scope| {
+            for t in 0..THREADS {
+                let filter = &filter;
+                scope.spawn(move || {
+                    for i in t * ITEMS_PER_THREAD..(t + 1) * ITEMS_PER_THREAD {
+                        filter.insert(&i);
+                    }
+                });
+            }
+        });
+        for i in 0..THREADS * ITEMS_PER_THREAD {
+            sequential.insert(&i);
+        }
+
+        assert_eq!(filter.len(), THREADS * ITEMS_PER_THREAD);
+        assert!(filter
+            .words
+            .iter()
+            .zip(&sequential.words)
+            .all(|(a, b)| a.load(Ordering::Relaxed) == b.load(Ordering::Relaxed)));
+        assert!((0..THREADS * ITEMS_PER_THREAD).all(|i| filter.might_contain(&i)));
+    }
+
+    #[test]
+    fn test_concurrent_queries() {
+        let filter = AtomicBloomFilter::with_seed(0.01_f64, 100_000, 42);
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..100_000 {
+                    filter.insert(&i);
+                }
+            });
+            scope.spawn(|| {
+                // Waits for every item to be found while they are being inserted, which only
+                // finishes if none of their bits are lost.
+                let deadline = Instant::now() + Duration::from_secs(30);
+                let mut i = 0;
+                while i < 100_000 {
+                    assert!(Instant::now() < deadline, "item {} was never found", i);
+                    if filter.might_contain(&i) {
+                        i += 1;
+                    }
+                }
+            });
+        });
+    }
+
+    #[test]
+    fn test_matches_bloom_filter() {
+        let filter = AtomicBloomFilter::with_seed(0.01_f64, 1000, 42);
+        let mut sequential = BloomFilter::with_seed(0.01_f64, 1000, 42);
+
+        for i in 0..500 {
+            filter.insert(&i);
+            sequential.add(&i);
+        }
+
+        assert_eq!(filter.num_bits(), sequential.num_bits());
+        assert_eq!(filter.num_hashes(), sequential.num_hashes());
+        assert!((0..10_000).all(|i| filter.might_contain(&i) == sequential.might_contain(&i)));
+    }
+}
@@ -0,0 +1,577 @@
+//! `BinaryFuseFilter` is an immutable filter that is built once from a known set of items, and
+//! can only be queried afterwards. In exchange, it needs much less space than a [`BloomFilter`]
+//! with the same false-positive rate, and every query reads exactly three fingerprints.
+//!
+//! The filter stores an array of fingerprints, such that the XOR of the three fingerprints that
+//! an item maps to equals the item's own fingerprint. Unlike an XOR filter, which spreads the
+//! three fingerprints across the whole array, a binary fuse filter picks them from three
+//! consecutive segments of the array. This lets the array be filled to about 89% for large sets,
+//! compared to 81% for an XOR filter, so each item takes about 1.13 times the size of its
+//! fingerprint. Smaller sets need relatively more space.
+//!
+//! The false-positive rate is determined by the width of the fingerprints alone: about 0.4% for
+//! 8-bit fingerprints, and about 0.0015% for 16-bit fingerprints.
+//!
+//! # References
+//! - [Binary Fuse Filters: Fast and Smaller Than Xor Filters](https://arxiv.org/abs/2201.01174)
+//! - [Xor Filters: Faster and Smaller Than Bloom and Cuckoo
+//!   Filters](https://arxiv.org/abs/1912.08258)
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+
+use crate::bloom_filter::fmix64;
+use crate::error::FlitError;
+use crate::fingerprint::FingerprintWidth;
+use crate::hash::XxHashBuilder;
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// The number of fingerprints that each item maps to.
+const ARITY: usize = 3;
+
+/// The largest number of fingerprints in a single segment.
+const MAX_SEGMENT_LENGTH: usize = 1 << 18;
+
+/// The number of times construction is retried with a different salt before giving up.
+const MAX_ITERATIONS: usize = 100;
+
+/// The attempt at which duplicate items are removed before construction is retried, if it has
+/// not succeeded yet.
+const DEDUPLICATE_ITERATION: usize = 10;
+
+/// The initial state of the generator for the salts tried during construction.
+const SALT_GENERATOR_STATE: u64 = 0x726b_2b9d_438b_9d4d;
+
+/// Represents a binary fuse filter.
+///
+/// The filter is constructed from a slice of items using `new`, and cannot be changed afterwards -
+/// create a new filter instead. Duplicate items are allowed, and are only stored once.
+///
+/// # Example
+/// ```rust
+/// use flit::BinaryFuseFilter;
+///
+/// let blocklist = ["Dogs are cool!", "Cats are cool!"];
+/// let filter = BinaryFuseFilter::new(&blocklist);
+///
+/// assert_eq!(filter.might_contain(&"Dogs are cool!"), true); // probably true
+/// assert_eq!(filter.might_contain(&"Hello, world!"), false); // definitely false!
+/// ```
+pub struct BinaryFuseFilter<T, S = XxHashBuilder> {
+    n: u64,
+    salt: u64,
+    segment_length: u64,
+    segment_count_length: u64,
+    fingerprints: Fingerprints,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Hash> BinaryFuseFilter<T> {
+    /// Creates a new binary fuse filter with 8-bit fingerprints, which contains `items`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the filter would be too large to allocate, or if it could not
+    /// be constructed from `items`.
+    pub fn new(items: &[T]) -> Self {
+        Self::with_fingerprint_width(items, FingerprintWidth::default())
+    }
+
+    /// Creates a new binary fuse filter whose fingerprints are `fingerprint_width` bits wide,
+    /// which contains `items`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the filter would be too large to allocate, or if it could not
+    /// be constructed from `items`.
+    pub fn with_fingerprint_width(items: &[T], fingerprint_width: FingerprintWidth) -> Self {
+        Self::with_fingerprint_width_and_hasher(items, fingerprint_width, XxHashBuilder::random())
+    }
+
+    /// Creates a new binary fuse filter with 8-bit fingerprints, which hashes items using the
+    /// given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the filter would be too large to allocate, or if it could not
+    /// be constructed from `items`.
+    pub fn with_seed(items: &[T], seed: u64) -> Self {
+        Self::with_fingerprint_width_and_seed(items, FingerprintWidth::default(), seed)
+    }
+
+    /// Creates a new binary fuse filter whose fingerprints are `fingerprint_width` bits wide,
+    /// which hashes items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the filter would be too large to allocate, or if it could not
+    /// be constructed from `items`.
+    pub fn with_fingerprint_width_and_seed(
+        items: &[T],
+        fingerprint_width: FingerprintWidth,
+        seed: u64,
+    ) -> Self {
+        Self::with_fingerprint_width_and_hasher(
+            items,
+            fingerprint_width,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BinaryFuseFilter<T, S> {
+    /// Creates a new binary fuse filter with 8-bit fingerprints, which hashes items using the
+    /// given `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the filter would be too large to allocate, or if it could not
+    /// be constructed from `items`.
+    pub fn with_hasher(items: &[T], build_hasher: S) -> Self {
+        Self::with_fingerprint_width_and_hasher(items, FingerprintWidth::default(), build_hasher)
+    }
+
+    /// Creates a new binary fuse filter whose fingerprints are `fingerprint_width` bits wide,
+    /// which hashes items using the given `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the filter would be too large to allocate, or if it could not
+    /// be constructed from `items`. Use `try_with_fingerprint_width_and_hasher` to handle these
+    /// cases without panicking.
+    pub fn with_fingerprint_width_and_hasher(
+        items: &[T],
+        fingerprint_width: FingerprintWidth,
+        build_hasher: S,
+    ) -> Self {
+        Self::try_with_fingerprint_width_and_hasher(items, fingerprint_width, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new binary fuse filter like `with_fingerprint_width_and_hasher`, but returns an
+    /// error instead of panicking if the filter cannot be constructed.
+    ///
+    /// Construction is retried with different salts until it succeeds, so it is extremely
+    /// unlikely to fail.
+    pub fn try_with_fingerprint_width_and_hasher(
+        items: &[T],
+        fingerprint_width: FingerprintWidth,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        let size = items.len();
+
+        let segment_length = if size == 0 {
+            4
+        } else {
+            let exponent = ((size as f64).ln() / 3.33_f64.ln() + 2.25).floor() as u32;
+            1_usize
+                .checked_shl(exponent)
+                .map_or(MAX_SEGMENT_LENGTH, |length| length.min(MAX_SEGMENT_LENGTH))
+        };
+
+        // Small sets need proportionally more room for construction to succeed.
+        let capacity = if size <= 1 {
+            0
+        } else {
+            let size_factor = (0.875 + 0.25 * 1e6_f64.ln() / (size as f64).ln()).max(1.125);
+            (size as f64 * size_factor).round() as usize
+        };
+
+        let segment_count = capacity.div_ceil(segment_length).max(ARITY) - (ARITY - 1);
+        let array_length = segment_count
+            .checked_add(ARITY - 1)
+            .and_then(|segments| segments.checked_mul(segment_length))
+            .filter(|&length| u32::try_from(length).is_ok())
+            .ok_or(FlitError::CapacityOverflow)?;
+
+        let mut filter = BinaryFuseFilter {
+            n: size as u64,
+            salt: 0,
+            segment_length: segment_length as u64,
+            segment_count_length: (segment_count * segment_length) as u64,
+            fingerprints: Fingerprints::new(fingerprint_width, array_length),
+            build_hasher,
+            _phantom: PhantomData,
+        };
+
+        let item_hashes: Vec<u64> = items
+            .iter()
+            .map(|item| filter.build_hasher.hash_one(item))
+            .collect();
+        filter.populate(item_hashes, segment_count)?;
+
+        Ok(filter)
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely does not contain the item.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        let hash = fmix64(self.build_hasher.hash_one(item).wrapping_add(self.salt));
+        let [h0, h1, h2] = self.indices(hash);
+
+        self.fingerprint(hash)
+            ^ self.fingerprints.get(h0)
+            ^ self.fingerprints.get(h1)
+            ^ self.fingerprints.get(h2)
+            == 0
+    }
+}
+
+impl<T, S> BinaryFuseFilter<T, S> {
+    /// Returns the number of items that the filter was constructed from.
+    pub fn len(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns `true` if the filter was constructed from no items.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn num_bits(&self) -> u64 {
+        self.fingerprints.len() as u64 * u64::from(self.fingerprints.bits())
+    }
+
+    /// Returns the number of bits that the filter uses for each item, or 0 if the filter was
+    /// constructed from no items.
+    pub fn bits_per_item(&self) -> f64 {
+        if self.n == 0 {
+            return 0_f64;
+        }
+
+        self.num_bits() as f64 / self.n as f64
+    }
+
+    /// Returns the expected false positive rate, which only depends on the width of the
+    /// fingerprints.
+    pub fn false_positive_rate(&self) -> f64 {
+        1_f64 / (self.fingerprints.max_value() as f64 + 1_f64)
+    }
+
+    /// Returns the fingerprint that is stored for an item with the given `hash`.
+    fn fingerprint(&self, hash: u64) -> u64 {
+        (hash ^ (hash >> 32)) & self.fingerprints.max_value()
+    }
+
+    /// Returns the indices of the three fingerprints for an item with the given `hash`, one in
+    /// each of three consecutive segments.
+    fn indices(&self, hash: u64) -> [usize; 3] {
+        let h0 = ((u128::from(hash) * u128::from(self.segment_count_length)) >> 64) as u64;
+        let h1 = (h0 + self.segment_length) ^ ((hash >> 18) & (self.segment_length - 1));
+        let h2 = (h0 + 2 * self.segment_length) ^ (hash & (self.segment_length - 1));
+
+        [h0 as usize, h1 as usize, h2 as usize]
+    }
+
+    /// Fills in the fingerprints so that every item in `item_hashes` is contained in the filter.
+    ///
+    /// This repeatedly finds a fingerprint that only a single item maps to, and removes that
+    /// item, until no items are left. The fingerprints are then assigned in the reverse order,
+    /// so that each item's fingerprint is always the last of its three to be assigned. If the
+    /// items cannot all be removed, construction is retried with a different salt.
+    fn populate(
+        &mut self,
+        mut item_hashes: Vec<u64>,
+        segment_count: usize,
+    ) -> Result<(), FlitError> {
+        let mut size = item_hashes.len();
+        let array_length = self.fingerprints.len();
+
+        // Items are first sorted roughly by segment, which makes construction much more cache
+        // friendly. A non-zero value marks the end of the array.
+        let mut reverse_order = vec![0_u64; size + 1];
+        reverse_order[size] = 1;
+        let mut reverse_h = vec![0_u8; size];
+
+        // For each fingerprint, the number of items that map to it (times 4, plus which of the
+        // item's three fingerprints it is, XOR-ed together), and the XOR of their hashes.
+        let mut t2count = vec![0_u8; array_length];
+        let mut t2hash = vec![0_u64; array_length];
+        let mut alone = vec![0_u32; array_length];
+
+        let mut block_bits = 1;
+        while (1 << block_bits) < segment_count {
+            block_bits += 1;
+        }
+        let block_mask = (1 << block_bits) - 1;
+        let mut start_pos = vec![0_usize; 1 << block_bits];
+
+        let mut salt_state = SALT_GENERATOR_STATE;
+        let mut stack_size;
+        let mut duplicates;
+        let mut iterations = 0;
+
+        loop {
+            if iterations == MAX_ITERATIONS {
+                return Err(FlitError::ConstructionFailed);
+            }
+            iterations += 1;
+
+            // Duplicates are dropped as they are found below, but only if no other item shares
+            // their fingerprints, which is rare in large filters. If construction keeps failing,
+            // remove them up front instead.
+            if iterations == DEDUPLICATE_ITERATION {
+                item_hashes.sort_unstable();
+                item_hashes.dedup();
+                size = item_hashes.len();
+                reverse_order[size] = 1;
+            }
+
+            self.salt = splitmix64(&mut salt_state);
+
+            for (i, pos) in start_pos.iter_mut().enumerate() {
+                *pos = ((i as u64 * size as u64) >> block_bits) as usize;
+            }
+            for &item_hash in &item_hashes {
+                let hash = fmix64(item_hash.wrapping_add(self.salt));
+                let mut segment = (hash >> (64 - block_bits)) as usize;
+                while reverse_order[start_pos[segment]] != 0 {
+                    segment = (segment + 1) & block_mask;
+                }
+                reverse_order[start_pos[segment]] = hash;
+                start_pos[segment] += 1;
+            }
+
+            let mut error = false;
+            duplicates = 0;
+            for &hash in &reverse_order[..size] {
+                let [h0, h1, h2] = self.indices(hash);
+
+                for (found, &index) in [h0, h1, h2].iter().enumerate() {
+                    t2count[index] = t2count[index].wrapping_add(4) ^ found as u8;
+                    t2hash[index] ^= hash;
+                }
+
+                // Two copies of the same item cancel each other out, so one of them is dropped.
+                if t2hash[h0] & t2hash[h1] & t2hash[h2] == 0
+                    && [h0, h1, h2]
+                        .iter()
+                        .any(|&index| t2hash[index] == 0 && t2count[index] == 8)
+                {
+                    duplicates += 1;
+                    for (found, &index) in [h0, h1, h2].iter().enumerate() {
+                        t2count[index] = t2count[index].wrapping_sub(4) ^ found as u8;
+                        t2hash[index] ^= hash;
+                    }
+                }
+
+                // The count wrapped around, so too many items map to the same fingerprint.
+                error |= [h0, h1, h2].iter().any(|&index| t2count[index] < 4);
+            }
+
+            if !error {
+                let mut queue = 0;
+                for (i, &count) in t2count.iter().enumerate() {
+                    alone[queue] = i as u32;
+                    if count >> 2 == 1 {
+                        queue += 1;
+                    }
+                }
+
+                stack_size = 0;
+                while queue > 0 {
+                    queue -= 1;
+                    let index = alone[queue] as usize;
+                    if t2count[index] >> 2 != 1 {
+                        continue;
+                    }
+
+                    let hash = t2hash[index];
+                    let found = t2count[index] & 3;
+                    reverse_h[stack_size] = found;
+                    reverse_order[stack_size] = hash;
+                    stack_size += 1;
+
+                    let [h0, h1, h2] = self.indices(hash);
+                    let h012 = [h0, h1, h2, h0, h1];
+                    for offset in 1..ARITY {
+                        let other = h012[found as usize + offset];
+                        alone[queue] = other as u32;
+                        if t2count[other] >> 2 == 2 {
+                            queue += 1;
+                        }
+                        t2count[other] =
+                            t2count[other].wrapping_sub(4) ^ ((found + offset as u8) % 3);
+                        t2hash[other] ^= hash;
+                    }
+                }
+
+                if stack_size + duplicates == size {
+                    break;
+                }
+            }
+
+            reverse_order[..size].iter_mut().for_each(|hash| *hash = 0);
+            t2count.iter_mut().for_each(|count| *count = 0);
+            t2hash.iter_mut().for_each(|hash| *hash = 0);
+        }
+
+        for i in (0..stack_size).rev() {
+            let hash = reverse_order[i];
+            let found = reverse_h[i] as usize;
+            let [h0, h1, h2] = self.indices(hash);
+            let h012 = [h0, h1, h2, h0, h1];
+
+            let fingerprint = self.fingerprint(hash)
+                ^ self.fingerprints.get(h012[found + 1])
+                ^ self.fingerprints.get(h012[found + 2]);
+            self.fingerprints.set(h012[found], fingerprint);
+        }
+
+        Ok(())
+    }
+}
+
+/// The fingerprints of a filter, stored as native integers of the fingerprint width, so that
+/// reading one is a single load.
+enum Fingerprints {
+    Eight(Vec<u8>),
+    Sixteen(Vec<u16>),
+    ThirtyTwo(Vec<u32>),
+}
+
+impl Fingerprints {
+    /// Creates `len` zeroed fingerprints, each `width` bits wide.
+    fn new(width: FingerprintWidth, len: usize) -> Self {
+        match width {
+            FingerprintWidth::Eight => Fingerprints::Eight(vec![0; len]),
+            FingerprintWidth::Sixteen => Fingerprints::Sixteen(vec![0; len]),
+            FingerprintWidth::ThirtyTwo => Fingerprints::ThirtyTwo(vec![0; len]),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Fingerprints::Eight(fingerprints) => fingerprints.len(),
+            Fingerprints::Sixteen(fingerprints) => fingerprints.len(),
+            Fingerprints::ThirtyTwo(fingerprints) => fingerprints.len(),
+        }
+    }
+
+    /// Returns the width of each fingerprint in bits.
+    fn bits(&self) -> u32 {
+        match self {
+            Fingerprints::Eight(_) => 8,
+            Fingerprints::Sixteen(_) => 16,
+            Fingerprints::ThirtyTwo(_) => 32,
+        }
+    }
+
+    /// Returns the largest fingerprint that can be stored.
+    fn max_value(&self) -> u64 {
+        (1 << self.bits()) - 1
+    }
+
+    fn get(&self, index: usize) -> u64 {
+        match self {
+            Fingerprints::Eight(fingerprints) => u64::from(fingerprints[index]),
+            Fingerprints::Sixteen(fingerprints) => u64::from(fingerprints[index]),
+            Fingerprints::ThirtyTwo(fingerprints) => u64::from(fingerprints[index]),
+        }
+    }
+
+    /// Stores `value`, which must not be larger than `max_value`, at `index`.
+    fn set(&mut self, index: usize, value: u64) {
+        match self {
+            Fingerprints::Eight(fingerprints) => fingerprints[index] = value as u8,
+            Fingerprints::Sixteen(fingerprints) => fingerprints[index] = value as u16,
+            Fingerprints::ThirtyTwo(fingerprints) => fingerprints[index] = value as u32,
+        }
+    }
+}
+
+/// Advances the SplitMix64 generator in `state`, and returns its next output.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains_all_items() {
+        for &size in &[0_u64, 1, 2, 10, 1000, 100_000] {
+            let items: Vec<u64> = (0..size).collect();
+            let filter = BinaryFuseFilter::new(&items);
+
+            assert_eq!(filter.len(), size);
+            assert!(items.iter().all(|i| filter.might_contain(i)));
+        }
+    }
+
+    #[test]
+    fn test_duplicates() {
+        let items = ["Hello, world!", "Hello, world!", "Dogs are cool!"];
+        let filter = BinaryFuseFilter::new(&items);
+
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(filter.might_contain(&"Dogs are cool!"));
+        assert!(!filter.might_contain(&"Cats are cool!"));
+    }
+
+    #[test]
+    fn test_many_duplicates() {
+        // Most of these duplicates share their fingerprints with other items, so they cannot be
+        // dropped during construction.
+        let items: Vec<u64> = (0..100_000).chain(0..1000).collect();
+        let filter = BinaryFuseFilter::with_seed(&items, 42);
+
+        assert!(items.iter().all(|i| filter.might_contain(i)));
+    }
+
+    #[test]
+    fn test_bits_per_item() {
+        assert_eq!(BinaryFuseFilter::<u64>::new(&[]).bits_per_item(), 0_f64);
+
+        let items: Vec<u64> = (0..1_000_000).collect();
+
+        let filter = BinaryFuseFilter::new(&items);
+        assert!(filter.bits_per_item() < 8_f64 * 1.14);
+
+        let filter = BinaryFuseFilter::with_fingerprint_width(&items, FingerprintWidth::Sixteen);
+        assert!(filter.bits_per_item() < 16_f64 * 1.14);
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let items: Vec<u64> = (0..100_000).collect();
+
+        let filter = BinaryFuseFilter::with_seed(&items, 42);
+        let false_positives = (100_000..1_100_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+        assert_eq!(filter.false_positive_rate(), 1_f64 / 256_f64);
+        assert!(false_positives < 4300);
+
+        let filter = BinaryFuseFilter::with_fingerprint_width_and_seed(
+            &items,
+            FingerprintWidth::Sixteen,
+            42,
+        );
+        let false_positives = (100_000..1_100_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+        assert!(false_positives < 30);
+    }
+}
@@ -0,0 +1,351 @@
+//! `BlockedBloomFilter` is a variant of a Bloom filter that is split into 512-bit blocks, each
+//! the size of a typical CPU cache line. Each item is mapped to a single block, and all of its
+//! bits are set within that block, so checking for an item causes at most one cache miss instead
+//! of up to `k`.
+//!
+//! Since items are not spread evenly across the blocks, some blocks fill up more than others,
+//! which increases the false-positive rate compared to a [`BloomFilter`] of the same size. The
+//! filter compensates for this by allocating slightly more bits than a `BloomFilter` would.
+//!
+//! # References
+//! - [Cache-, Hash- and Space-Efficient Bloom
+//!   Filters](https://algo2.iti.kit.edu/documents/cacheefficientbloomfilters-jea.pdf)
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+
+use crate::bloom_filter::{optimal_parameters, split_hash};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::mem;
+
+/// The number of bits in each block.
+const BLOCK_BITS: u64 = 512;
+
+/// A single 512-bit block, aligned to a 64-byte cache line.
+#[derive(Clone, Copy, Default)]
+#[repr(align(64))]
+struct Block([u64; 8]);
+
+/// Represents a cache-line blocked Bloom filter.
+///
+/// When constructing the filter using `new`, you need to specify the desired acceptable
+/// false-positive rate, and the number of items you intend to store in the filter. Neither can be
+/// adjusted after creation - create a new filter instead.
+///
+/// # Example
+/// ```rust
+/// use flit::BlockedBloomFilter;
+///
+/// let mut filter = BlockedBloomFilter::new(0.01, 10000);
+/// filter.add(&"Hello, world!");
+///
+/// assert_eq!(filter.might_contain(&"Hello, world!"), true); // probably true
+/// assert_eq!(filter.might_contain(&"Dogs are cool!"), false); // definitely false!
+/// ```
+pub struct BlockedBloomFilter<T, S = XxHashBuilder> {
+    n: u64,
+    k: u32,
+    blocks: Vec<Block>,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Hash> BlockedBloomFilter<T> {
+    /// Creates a new blocked Bloom filter based on the required false positive rate and the
+    /// estimated number of items that will be added to the filter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_new` to handle these cases without panicking.
+    pub fn new(false_positive_rate: f64, estimated_items: usize) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new blocked Bloom filter like `new`, but hashes items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_seed` to handle these cases without panicking.
+    pub fn with_seed(false_positive_rate: f64, estimated_items: usize, seed: u64) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Creates a new blocked Bloom filter like `new`, but returns an error instead of panicking
+    /// if the parameters are invalid.
+    pub fn try_new(false_positive_rate: f64, estimated_items: usize) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new blocked Bloom filter like `with_seed`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_seed(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        seed: u64,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BlockedBloomFilter<T, S> {
+    /// Creates a new blocked Bloom filter like `new`, but hashes items using the given
+    /// `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_hasher` to handle these cases without panicking.
+    pub fn with_hasher(false_positive_rate: f64, estimated_items: usize, build_hasher: S) -> Self {
+        Self::try_with_hasher(false_positive_rate, estimated_items, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new blocked Bloom filter like `with_hasher`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_hasher(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        let (num_bits, num_hashes) = optimal_parameters(false_positive_rate, estimated_items)?;
+
+        // Start from the size of an equivalent `BloomFilter`, and grow the filter until the
+        // uneven distribution of items across blocks is compensated for.
+        let mut num_blocks = num_bits.div_ceil(BLOCK_BITS);
+        while blocked_false_positive_rate(estimated_items as u64, num_blocks, num_hashes)
+            > false_positive_rate
+        {
+            num_blocks += num_blocks.div_ceil(50);
+        }
+        if num_blocks > (isize::MAX as usize / mem::size_of::<Block>()) as u64 {
+            return Err(FlitError::CapacityOverflow);
+        }
+
+        Ok(BlockedBloomFilter {
+            n: 0,
+            k: num_hashes,
+            blocks: vec![Block::default(); num_blocks as usize],
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Adds the `item` to the filter by setting the appropriate bits in its block to `true`.
+    pub fn add(&mut self, item: &T) {
+        let (block, mask) = self.block_and_mask(item);
+        let block = &mut self.blocks[block].0;
+
+        for (word, mask) in block.iter_mut().zip(mask.iter()) {
+            *word |= mask;
+        }
+
+        self.n += 1;
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely does not contain the item.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        let (block, mask) = self.block_and_mask(item);
+        let block = &self.blocks[block].0;
+
+        block
+            .iter()
+            .zip(mask.iter())
+            .all(|(word, mask)| word & mask == *mask)
+    }
+
+    /// Returns the number of items that have been added to the filter.
+    pub fn len(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns `true` if no items have been added to the filter.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Returns the number of bits in the filter.
+    pub fn num_bits(&self) -> u64 {
+        self.blocks.len() as u64 * BLOCK_BITS
+    }
+
+    /// Returns the number of bits set for each item.
+    pub fn num_hashes(&self) -> u32 {
+        self.k
+    }
+
+    /// Calculates the current expected false positive rate given the number of items in the
+    /// filter, taking the uneven distribution of items across blocks into account.
+    pub fn false_positive_rate(&self) -> f64 {
+        blocked_false_positive_rate(self.n, self.blocks.len() as u64, self.k)
+    }
+
+    /// Returns the index of the block for `item`, and the bits to set within that block.
+    fn block_and_mask(&self, item: &T) -> (usize, [u64; 8]) {
+        let (h1, h2) = split_hash(item, &self.build_hasher);
+
+        // Maps `h1` onto `0..num_blocks` without a division. See Lemire, [A fast alternative to
+        // the modulo reduction](https://lemire.me/blog/2016/06/27/a-fast-alternative-to-the-modulo-reduction/).
+        let block = ((u128::from(h1) * self.blocks.len() as u128) >> 64) as usize;
+
+        // Each position is taken from the top 9 bits of `h2`, which is then remixed by
+        // multiplying it with an odd constant. Unlike double hashing, this doesn't make the
+        // positions of two items in the same block fall into the same arithmetic progression.
+        let mut hash = h2;
+        let mut mask = [0; 8];
+        for _ in 0..self.k {
+            let bit = hash >> (64 - BLOCK_BITS.trailing_zeros());
+            mask[(bit / 64) as usize] |= 1 << (bit % 64);
+            hash = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        }
+
+        (block, mask)
+    }
+}
+
+/// Calculates the expected false positive rate of a blocked filter with `num_blocks` blocks and
+/// `k` hashes, after `n` items have been added to it.
+///
+/// The number of items in each block follows a Poisson distribution, so the false positive rate is
+/// the average false positive rate of a single block, weighted by the probability of the block
+/// holding that many items.
+///
+/// The probabilities are calculated relative to the most likely number of items, and normalized
+/// at the end, since `e^-λ` underflows to zero for heavily loaded filters.
+fn blocked_false_positive_rate(n: u64, num_blocks: u64, k: u32) -> f64 {
+    let items_per_block = n as f64 / num_blocks as f64;
+    let bit_unset = 1_f64 - 1_f64 / BLOCK_BITS as f64;
+    let block_rate = |i: u32| (1_f64 - bit_unset.powf(f64::from(k) * f64::from(i))).powi(k as i32);
+
+    // Terms further than this from the mode are too unlikely to make a difference.
+    let spread = 10_f64 * items_per_block.sqrt() + 10_f64;
+    let mode = items_per_block.floor();
+    let min_items = (mode - spread).max(0_f64) as u32;
+    let max_items = (mode + spread).ceil() as u32;
+    let mode = mode as u32;
+
+    let mut total_weight = 0_f64;
+    let mut false_positive_rate = 0_f64;
+
+    let mut weight = 1_f64;
+    for i in mode..=max_items {
+        total_weight += weight;
+        false_positive_rate += weight * block_rate(i);
+        weight *= items_per_block / f64::from(i + 1);
+    }
+
+    let mut weight = 1_f64;
+    for i in (min_items..mode).rev() {
+        weight *= f64::from(i + 1) / items_per_block;
+        total_weight += weight;
+        false_positive_rate += weight * block_rate(i);
+    }
+
+    false_positive_rate / total_weight
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_new() {
+        assert!(matches!(
+            BlockedBloomFilter::<&str>::try_new(1.5_f64, 10),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            BlockedBloomFilter::<&str>::try_new(0.01_f64, 0),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(matches!(
+            BlockedBloomFilter::<&str>::try_new(0.01_f64, usize::MAX),
+            Err(FlitError::CapacityOverflow)
+        ));
+        assert!(BlockedBloomFilter::<&str>::try_with_seed(0.01_f64, 10, 42).is_ok());
+    }
+
+    #[test]
+    fn test_compensated_size() {
+        let filter = BlockedBloomFilter::<u32>::new(0.01_f64, 100_000);
+
+        // A `BloomFilter` would need 958,506 bits, which is not enough for a blocked filter.
+        let uncompensated_blocks = 958_506_u64.div_ceil(BLOCK_BITS);
+        assert!(blocked_false_positive_rate(100_000, uncompensated_blocks, 7) > 0.01);
+
+        assert!(filter.num_bits() > 958_506);
+        assert_eq!(filter.num_hashes(), 7);
+        assert!(blocked_false_positive_rate(100_000, filter.blocks.len() as u64, 7) <= 0.01);
+    }
+
+    #[test]
+    fn test_saturated_false_positive_rate() {
+        let mut filter = BlockedBloomFilter::with_seed(0.01_f64, 10, 42);
+
+        for i in 0..100_000 {
+            filter.add(&i);
+        }
+
+        assert!(filter.false_positive_rate() > 0.99);
+        assert!(filter.false_positive_rate() <= 1_f64);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut filter = BlockedBloomFilter::new(0.03_f64, 10);
+
+        filter.add(&"Hello, world!");
+
+        assert!(filter.false_positive_rate() > 0.0);
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(!filter.might_contain(&"Dogs are cool!"));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = BlockedBloomFilter::with_seed(0.01_f64, 100_000, 42);
+
+        for i in 0..100_000 {
+            filter.add(&i);
+        }
+
+        let false_positives = (100_000..200_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+
+        assert!((0..100_000).all(|i| filter.might_contain(&i)));
+        assert!(filter.false_positive_rate() <= 0.01);
+        assert!(false_positives < 1100);
+    }
+}
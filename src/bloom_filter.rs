@@ -3,8 +3,8 @@
 //! item, i.e., the data structure has an inherent false-positive rate greater than 0%.
 //!
 //! Items can be added to the Bloom filter, but cannot be removed - this would introduce false
-//! negative cases. If this is required, an alternative might be to use a Counting Bloom filter
-//! (not (yet) implemented in this crate).
+//! negative cases. If this is required, an alternative might be to use a
+//! [`CountingBloomFilter`].
 //!
 //! This allows the filter to be very space-efficient.
 //!
@@ -13,25 +13,55 @@
 //!
 //! # References
 //! - [Less Hashing, Same Performance: Building a Better Bloom
-//! Filter](https://www.eecs.harvard.edu/~michaelm/postscripts/rsa2008.pdf)
+//!   Filter](https://www.eecs.harvard.edu/~michaelm/postscripts/rsa2008.pdf)
 //! - [Wikipedia article](https://en.wikipedia.org/wiki/Bloom_filter)
 //! - [Bloom Filters by Example](https://llimllib.github.io/bloomfilter-tutorial/)
 //! - [Bloom Filter Calculator](https://hur.st/bloomfilter/)
+//!
+//! [`CountingBloomFilter`]: ../counting_bloom_filter/struct.CountingBloomFilter.html
 
+use crate::error::{DecodeError, FlitError};
+use crate::hash::XxHashBuilder;
+use crate::serialization::{ChecksumReader, ChecksumWriter, HASHER_XXHASH64};
 use bitvec::bitvec;
+use bitvec::order::Lsb0;
+use bitvec::vec::BitVec;
+#[cfg(feature = "rayon")]
+use rayon::iter::{
+    IndexedParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator,
+};
+use std::borrow::Borrow;
+use std::convert::TryFrom;
 use std::f64::consts::{E, LN_2};
-use std::hash::{BuildHasher, Hash, Hasher};
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Read, Write};
 use std::marker::PhantomData;
-use twox_hash::RandomXxHashBuilder;
+use std::ops::{BitAnd, BitOr};
 
 const LN2_SQUARED: f64 = LN_2 * LN_2;
 
+/// Identifies a serialized `BloomFilter`.
+const MAGIC: [u8; 4] = *b"FLBF";
+
+/// The current version of the serialization format.
+const FORMAT_VERSION: u8 = 1;
+
+/// The largest number of bits that a `BitVec` can hold.
+const MAX_BITS: u64 = (usize::MAX >> 3) as u64;
+
 /// Represents a Bloom filter.
 ///
 /// When constructing the filter using `new`, you need to specify the desired acceptable
 /// false-positive rate, and the number of items you intend to store in the filter. Neither can be
 /// adjusted after creation - create a new filter instead.
 ///
+/// Filters created with `new` hash their items using a random seed, so two filters will not agree
+/// on which bits represent an item. Use `with_seed` to create filters that can be shared between
+/// processes.
+///
+/// Like `HashMap`, the filter is generic over the `BuildHasher` used to hash its items, which
+/// defaults to xxHash. A different hasher can be provided using `with_hasher`.
+///
 /// # Example
 /// ```rust
 /// use flit::BloomFilter;
@@ -42,12 +72,12 @@ const LN2_SQUARED: f64 = LN_2 * LN_2;
 /// assert_eq!(filter.might_contain(&"Hello, world!"), true); // probably true
 /// assert_eq!(filter.might_contain(&"Dogs are cool!"), false); // definitely false!
 /// ```
-pub struct BloomFilter<T> {
+pub struct BloomFilter<T, S = XxHashBuilder> {
     n: u64,
     m: u64,
     k: u32,
-    bit_vec: bitvec::vec::BitVec,
-    build_hasher: RandomXxHashBuilder,
+    bit_vec: BitVec<Lsb0, u64>,
+    build_hasher: S,
     _phantom: PhantomData<T>,
 }
 
@@ -61,40 +91,508 @@ impl<T: Hash> BloomFilter<T> {
     /// # Panics
     ///
     /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
-    /// or if `estimated_items` is not greater than 0.
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_new` to handle these cases without panicking.
     pub fn new(false_positive_rate: f64, estimated_items: usize) -> Self {
-        assert!(
-            false_positive_rate > 0_f64 && false_positive_rate < 1_f64,
-            "False positive rate must be between 0 and 1 (non-inclusive)"
-        );
-        assert!(
-            estimated_items > 0,
-            "Number of estimated items must be greater than zero"
-        );
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::random(),
+        )
+    }
 
-        let num_bits = -(estimated_items as f64) * false_positive_rate.ln() / LN2_SQUARED;
-        let num_hashes = (num_bits / estimated_items as f64) * LN_2;
+    /// Creates a new Bloom filter like `new`, but hashes items using the given `seed`.
+    ///
+    /// Two filters created with the same parameters and seed set the same bits for the same
+    /// items, even if they were created by different processes.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_seed` to handle these cases without panicking.
+    pub fn with_seed(false_positive_rate: f64, estimated_items: usize, seed: u64) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
 
-        let num_bits = num_bits.ceil() as u64;
-        let num_hashes = num_hashes.ceil() as u32;
+    /// Creates a new Bloom filter like `new`, but returns an error instead of panicking if the
+    /// parameters are invalid.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::{BloomFilter, FlitError};
+    ///
+    /// let filter = BloomFilter::<&str>::try_new(1.5, 10000);
+    ///
+    /// assert!(matches!(filter, Err(FlitError::InvalidFalsePositiveRate(_))));
+    /// ```
+    pub fn try_new(false_positive_rate: f64, estimated_items: usize) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::random(),
+        )
+    }
 
-        BloomFilter {
+    /// Creates a new Bloom filter like `with_seed`, but returns an error instead of panicking if
+    /// the parameters are invalid.
+    pub fn try_with_seed(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        seed: u64,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Creates a new Bloom filter with the required false positive rate, sized for the items of
+    /// an iterator, and adds all of the items to it.
+    ///
+    /// If the iterator reports its exact length, the items are added as they are produced.
+    /// Otherwise, they are collected first, in order to count them.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    ///
+    /// let filter = BloomFilter::from_iter_with_rate(0.01, vec!["Hello, world!", "Dogs are cool!"]);
+    ///
+    /// assert_eq!(filter.len(), 2);
+    /// assert_eq!(filter.might_contain(&"Hello, world!"), true); // probably true
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if the iterator is empty, or if the filter would be too large to allocate. Use
+    /// `try_from_iter_with_rate` to handle these cases without panicking.
+    pub fn from_iter_with_rate<I>(false_positive_rate: f64, items: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+    {
+        Self::try_from_iter_with_rate(false_positive_rate, items)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Bloom filter like `from_iter_with_rate`, but returns an error instead of
+    /// panicking if the parameters are invalid, or if the iterator is empty.
+    pub fn try_from_iter_with_rate<I>(false_positive_rate: f64, items: I) -> Result<Self, FlitError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let items = items.into_iter();
+
+        match items.size_hint() {
+            (lower, Some(upper)) if lower == upper => {
+                let mut filter = Self::try_new(false_positive_rate, lower)?;
+                filter.extend(items);
+                Ok(filter)
+            }
+            _ => {
+                let items: Vec<T> = items.collect();
+
+                let mut filter = Self::try_new(false_positive_rate, items.len())?;
+                filter.extend(items);
+                Ok(filter)
+            }
+        }
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+
+    /// Serializes the filter into a byte vector, which can be turned back into a filter using
+    /// `from_bytes`.
+    ///
+    /// See `write_to` for a description of the format.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new(0.01, 10000);
+    /// filter.add(&"Hello, world!");
+    ///
+    /// let bytes = filter.to_bytes();
+    /// let filter = BloomFilter::<&str>::from_bytes(&bytes).unwrap();
+    ///
+    /// assert_eq!(filter.might_contain(&"Hello, world!"), true);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(42 + self.bit_vec.as_slice().len() * 8);
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec should never fail");
+        bytes
+    }
+
+    /// Serializes the filter into `writer`.
+    ///
+    /// The format consists of the following fields, with all integers in little-endian byte
+    /// order:
+    ///
+    /// | Field    | Size         | Description                                            |
+    /// |----------|--------------|--------------------------------------------------------|
+    /// | magic    | 4            | The bytes `FLBF`                                       |
+    /// | version  | 1            | The format version, currently 1                        |
+    /// | hasher   | 1            | The hasher used for items, currently always 1 (xxHash) |
+    /// | seed     | 8            | The seed of the hasher                                 |
+    /// | n        | 8            | The number of items added to the filter                |
+    /// | m        | 8            | The number of bits in the filter                       |
+    /// | k        | 4            | The number of hashes applied to each item              |
+    /// | bits     | 8 * ⌈m / 64⌉ | The bits of the filter, as 64-bit words                |
+    /// | checksum | 8            | xxHash64 (with seed 0) of all of the preceding bytes   |
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = ChecksumWriter::new(writer);
+
+        writer.write_all(&MAGIC)?;
+        writer.write_u8(FORMAT_VERSION)?;
+        writer.write_u8(HASHER_XXHASH64)?;
+        writer.write_u64(self.seed())?;
+        writer.write_u64(self.n)?;
+        writer.write_u64(self.m)?;
+        writer.write_u32(self.k)?;
+        for &word in self.bit_vec.as_slice() {
+            writer.write_u64(word)?;
+        }
+
+        writer.finish()
+    }
+
+    /// Deserializes a filter previously serialized using `to_bytes` or `write_to`.
+    ///
+    /// Returns an error if `bytes` does not contain exactly one valid filter.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        let filter = Self::read_from(&mut bytes)?;
+
+        if !bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(filter)
+    }
+
+    /// Deserializes a filter previously serialized using `to_bytes` or `write_to` from `reader`.
+    ///
+    /// Only the bytes belonging to the filter are consumed from `reader`.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, DecodeError> {
+        let mut reader = ChecksumReader::new(reader);
+
+        if reader.read_array::<4>()? != MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        let version = reader.read_u8()?;
+        if version != FORMAT_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+
+        let hasher = reader.read_u8()?;
+        if hasher != HASHER_XXHASH64 {
+            return Err(DecodeError::UnsupportedHasher(hasher));
+        }
+
+        let seed = reader.read_u64()?;
+        let n = reader.read_u64()?;
+        let m = reader.read_u64()?;
+        let k = reader.read_u32()?;
+
+        let num_words = usize::try_from(m.div_ceil(64)).map_err(|_| DecodeError::InvalidData)?;
+        // Don't trust the header with a huge up-front allocation; a truncated input will fail
+        // long before the vector grows that large.
+        let mut words = Vec::with_capacity(num_words.min(1 << 16));
+        for _ in 0..num_words {
+            words.push(reader.read_u64()?);
+        }
+
+        if !reader.verify()? {
+            return Err(DecodeError::ChecksumMismatch);
+        }
+
+        Self::from_parts(seed, n, m, k, words).ok_or(DecodeError::InvalidData)
+    }
+}
+
+impl<T> BloomFilter<T> {
+    /// Reassembles a deserialized filter from its parts, or returns `None` if they are
+    /// inconsistent with each other.
+    fn from_parts(seed: u64, n: u64, m: u64, k: u32, words: Vec<u64>) -> Option<Self> {
+        let num_bits = usize::try_from(m).ok()?;
+        if num_bits == 0 || k == 0 || words.len() != num_bits.div_ceil(64) {
+            return None;
+        }
+
+        // The unused bits at the end of the last word must be zero.
+        let unused_bits = words.len() * 64 - num_bits;
+        if unused_bits > 0 && words[words.len() - 1] >> (64 - unused_bits) != 0 {
+            return None;
+        }
+
+        let mut bit_vec = BitVec::from_vec(words);
+        bit_vec.truncate(num_bits);
+
+        Some(BloomFilter {
+            n,
+            m,
+            k,
+            bit_vec,
+            build_hasher: XxHashBuilder::with_seed(seed),
+            _phantom: PhantomData,
+        })
+    }
+}
+
+/// The representation of a `BloomFilter` used by serde.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "BloomFilter")]
+struct SerdeBloomFilter<'a> {
+    seed: u64,
+    n: u64,
+    m: u64,
+    k: u32,
+    bits: std::borrow::Cow<'a, [u64]>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for BloomFilter<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerdeBloomFilter {
+            seed: self.build_hasher.seed(),
+            n: self.n,
+            m: self.m,
+            k: self.k,
+            bits: self.bit_vec.as_slice().into(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for BloomFilter<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let filter = SerdeBloomFilter::deserialize(deserializer)?;
+
+        Self::from_parts(
+            filter.seed,
+            filter.n,
+            filter.m,
+            filter.k,
+            filter.bits.into_owned(),
+        )
+        .ok_or_else(|| serde::de::Error::custom(DecodeError::InvalidData))
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Hash + Send> BloomFilter<T> {
+    /// Creates a new Bloom filter with the required false positive rate, sized for the items of
+    /// a parallel iterator, and adds all of the items to it in parallel.
+    ///
+    /// Like `new`, the filter hashes items using a random seed. Use `with_seed` followed by
+    /// `par_extend` to build a filter with a given seed.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    ///
+    /// let filter = BloomFilter::from_par_iter(0.01, 0..100_000);
+    ///
+    /// assert_eq!(filter.len(), 100_000);
+    /// assert_eq!(filter.might_contain(&42), true);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if the iterator is empty, or if the filter would be too large to allocate. Use
+    /// `try_from_par_iter` to handle these cases without panicking.
+    pub fn from_par_iter<I>(false_positive_rate: f64, items: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+        I::Iter: IndexedParallelIterator,
+    {
+        Self::try_from_par_iter(false_positive_rate, items).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Bloom filter like `from_par_iter`, but returns an error instead of panicking
+    /// if the parameters are invalid, or if the iterator is empty.
+    pub fn try_from_par_iter<I>(false_positive_rate: f64, items: I) -> Result<Self, FlitError>
+    where
+        I: IntoParallelIterator<Item = T>,
+        I::Iter: IndexedParallelIterator,
+    {
+        let items = items.into_par_iter();
+
+        let mut filter = Self::try_new(false_positive_rate, items.len())?;
+        filter.par_extend(items);
+        Ok(filter)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T, S: BuildHasher + Sync> BloomFilter<T, S> {
+    /// Adds every item of a parallel iterator to the filter, hashing the items and setting their
+    /// bits on all threads at once.
+    ///
+    /// The bits are set atomically in place, so the result is exactly the same as adding the
+    /// items one at a time.
+    fn par_add_all<I, U>(&mut self, items: I)
+    where
+        I: ParallelIterator<Item = U>,
+        U: Borrow<T>,
+        T: Hash,
+    {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        let (m, k, build_hasher) = (self.m, self.k, &self.build_hasher);
+        let add_items = |words: &[AtomicU64]| {
+            items
+                .map(|item| {
+                    for i in indices_for_hash(split_hash(item.borrow(), build_hasher), m, k) {
+                        words[i / 64].fetch_or(1 << (i % 64), Ordering::Relaxed);
+                    }
+                })
+                .count()
+        };
+
+        let words = self.bit_vec.as_mut_slice();
+        let added = if words.as_ptr() as usize % std::mem::align_of::<AtomicU64>() == 0 {
+            // SAFETY: `AtomicU64` has the same size and bit validity as `u64`, and the alignment
+            // of the words was checked above. The words are borrowed mutably for as long as they
+            // are accessed as atomics, so they cannot be accessed in any other way meanwhile.
+            let words = unsafe {
+                std::slice::from_raw_parts(words.as_mut_ptr() as *const AtomicU64, words.len())
+            };
+            add_items(words)
+        } else {
+            // On targets where `u64` is less aligned than `AtomicU64`, the bits are set in a copy
+            // of the words instead.
+            let atomic_words: Vec<AtomicU64> =
+                words.iter().map(|&word| AtomicU64::new(word)).collect();
+            let added = add_items(&atomic_words);
+            for (word, atomic_word) in words.iter_mut().zip(atomic_words) {
+                *word = atomic_word.into_inner();
+            }
+            added
+        };
+
+        self.n += added as u64;
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<T: Hash + Send, S: BuildHasher + Sync> ParallelExtend<T> for BloomFilter<T, S> {
+    fn par_extend<I: IntoParallelIterator<Item = T>>(&mut self, items: I) {
+        self.par_add_all(items.into_par_iter());
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, T: 'a + Hash + Sync, S: BuildHasher + Sync> ParallelExtend<&'a T> for BloomFilter<T, S> {
+    fn par_extend<I: IntoParallelIterator<Item = &'a T>>(&mut self, items: I) {
+        self.par_add_all(items.into_par_iter());
+    }
+}
+
+impl<T: Hash, S: BuildHasher> BloomFilter<T, S> {
+    /// Creates a new Bloom filter like `new`, but hashes items using the given `build_hasher`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    /// use std::collections::hash_map::RandomState;
+    ///
+    /// let mut filter = BloomFilter::with_hasher(0.01, 10000, RandomState::new());
+    /// filter.add(&"Hello, world!");
+    ///
+    /// assert_eq!(filter.might_contain(&"Hello, world!"), true);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_hasher` to handle these cases without panicking.
+    pub fn with_hasher(false_positive_rate: f64, estimated_items: usize, build_hasher: S) -> Self {
+        Self::try_with_hasher(false_positive_rate, estimated_items, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Bloom filter like `with_hasher`, but returns an error instead of panicking if
+    /// the parameters are invalid.
+    pub fn try_with_hasher(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        let (num_bits, num_hashes) = optimal_parameters(false_positive_rate, estimated_items)?;
+
+        Self::from_parameters(num_bits, num_hashes, build_hasher)
+    }
+
+    /// Creates a new, empty Bloom filter with exactly `num_bits` bits and `num_hashes` hashes.
+    pub(crate) fn from_parameters(
+        num_bits: u64,
+        num_hashes: u32,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        debug_assert!(num_bits > 0 && num_hashes > 0);
+
+        if num_bits > MAX_BITS {
+            return Err(FlitError::CapacityOverflow);
+        }
+
+        Ok(BloomFilter {
             n: 0,
             m: num_bits,
             k: num_hashes,
-            bit_vec: bitvec![0; num_bits as usize],
-            build_hasher: RandomXxHashBuilder::default(),
+            bit_vec: bitvec![Lsb0, u64; 0; num_bits as usize],
+            build_hasher,
             _phantom: PhantomData,
-        }
+        })
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Returns the number of bits in the filter (`m`).
+    pub fn num_bits(&self) -> u64 {
+        self.m
+    }
+
+    /// Returns the number of hashes applied to each item (`k`).
+    pub fn num_hashes(&self) -> u32 {
+        self.k
     }
 
     /// Adds the `item` to the filter by setting the appropriate bits in the filter to `true`.
     pub fn add(&mut self, item: &T) {
-        for i in indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k) {
-            self.bit_vec.set(i, true);
-        }
+        self.add_split_hash(split_hash(item, &self.build_hasher));
+    }
 
-        self.n += 1;
+    /// Adds the `item` to the filter like `add`, but takes any borrowed form of the filter's
+    /// item type, such as a `&str` for a filter of `String`s.
+    ///
+    /// The borrowed form must hash the same way as the item type, like the keys of a `HashSet`.
+    ///
+    /// Unlike `might_contain`, `add` itself only takes the item type, so that the type of a new
+    /// filter can still be inferred from the items added to it.
+    pub fn add_borrowed<Q: ?Sized + Hash>(&mut self, item: &Q)
+    where
+        T: Borrow<Q>,
+    {
+        self.add_split_hash(split_hash(item, &self.build_hasher));
     }
 
     /// Checks if the filter *might* contain the `item`.
@@ -102,47 +600,433 @@ impl<T: Hash> BloomFilter<T> {
     /// If this function returns false, the filter definitely does not contain the item.
     /// If this function returns true, the filter *might* contain the item, but it might also be a
     /// false-positive.
-    pub fn might_contain(&self, item: &T) -> bool {
+    ///
+    /// The `item` may be any borrowed form of the filter's item type, such as a `&str` for a
+    /// filter of `String`s, as long as it hashes the same way, like the keys of a `HashSet`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::<String>::new(0.01, 10000);
+    /// filter.add_borrowed("Hello, world!");
+    ///
+    /// assert_eq!(filter.might_contain("Hello, world!"), true); // probably true
+    /// assert_eq!(filter.might_contain(&"Hello, world!".to_string()), true); // probably true
+    /// ```
+    pub fn might_contain<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.contains_split_hash(split_hash(item, &self.build_hasher))
+    }
+
+    /// Checks if the filter *might* contain the `item`, and then adds it.
+    ///
+    /// This is equivalent to calling `might_contain` followed by `add`, but only hashes the item
+    /// once, and checks and sets its bits in a single pass. Unlike `add`, the item is only counted
+    /// in `len` if the filter did not already contain it, so adding duplicates does not inflate
+    /// `false_positive_rate`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new(0.01, 10000);
+    ///
+    /// assert_eq!(filter.check_and_add(&"Hello, world!"), false); // definitely not seen before
+    /// assert_eq!(filter.check_and_add(&"Hello, world!"), true); // probably seen before
+    /// assert_eq!(filter.len(), 1);
+    /// ```
+    pub fn check_and_add(&mut self, item: &T) -> bool {
+        let mut present = true;
         for i in indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k) {
             if !self.bit_vec[i] {
-                return false;
+                self.bit_vec.set(i, true);
+                present = false;
             }
         }
 
-        true
+        if !present {
+            self.n += 1;
+        }
+        present
     }
 
     /// Calculates the current expected false positive rate given the number of items in the
     /// filter.
+    ///
+    /// Every call to `add` counts as a new item, so this overestimates the false positive rate if
+    /// items are added more than once. Add items using `check_and_add` to avoid this, or see
+    /// `estimated_false_positive_rate`.
     pub fn false_positive_rate(&self) -> f64 {
-        (1_f64 - E.powf(-1_f64 * f64::from(self.k) * self.n as f64 / self.m as f64))
-            .powi(self.k as i32)
+        false_positive_rate(self.n, self.m, self.k)
+    }
+}
+
+impl<T, S: PartialEq> BloomFilter<T, S> {
+    /// Adds all of the items in `other` to this filter, by setting every bit that is set in
+    /// `other`.
+    ///
+    /// The number of items in the resulting filter is estimated from its bits, since items that
+    /// were added to both filters would otherwise be counted twice.
+    ///
+    /// Returns an error, leaving this filter unchanged, if the filters do not have the same
+    /// number of bits, number of hashes and hasher.
+    ///
+    /// The hashers are compared using `PartialEq`, so filters can only be combined if their
+    /// hasher implements it. `XxHashBuilder`, the default, does, but many other hashers do not,
+    /// including `std::collections::hash_map::RandomState`.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    ///
+    /// let mut a = BloomFilter::with_seed(0.01, 10000, 42);
+    /// let mut b = BloomFilter::with_seed(0.01, 10000, 42);
+    /// a.add(&"Hello, world!");
+    /// b.add(&"Dogs are cool!");
+    ///
+    /// a.union_with(&b).unwrap();
+    ///
+    /// assert_eq!(a.might_contain(&"Hello, world!"), true);
+    /// assert_eq!(a.might_contain(&"Dogs are cool!"), true);
+    /// ```
+    pub fn union_with(&mut self, other: &Self) -> Result<(), FlitError> {
+        self.check_compatible(other)?;
+
+        for (word, other) in self
+            .bit_vec
+            .as_mut_slice()
+            .iter_mut()
+            .zip(other.bit_vec.as_slice())
+        {
+            *word |= other;
+        }
+
+        self.n = self.estimated_len().round() as u64;
+        Ok(())
+    }
+
+    /// Removes all of the items that are not also in `other` from this filter, by clearing every
+    /// bit that is not set in `other`.
+    ///
+    /// The resulting filter answers `might_contain` for items that might be in both filters. Its
+    /// number of items is estimated from the estimated sizes of both filters and of their union.
+    ///
+    /// Returns an error, leaving this filter unchanged, if the filters do not have the same
+    /// number of bits, number of hashes and hasher. Like `union_with`, this is only available if
+    /// the hasher implements `PartialEq`.
+    pub fn intersect_with(&mut self, other: &Self) -> Result<(), FlitError> {
+        self.check_compatible(other)?;
+
+        let self_len = self.estimated_len();
+        let other_len = other.estimated_len();
+
+        let mut union_ones = 0;
+        for (word, other) in self
+            .bit_vec
+            .as_mut_slice()
+            .iter_mut()
+            .zip(other.bit_vec.as_slice())
+        {
+            union_ones += u64::from((*word | other).count_ones());
+            *word &= other;
+        }
+
+        // |A ∩ B| = |A| + |B| - |A ∪ B|
+        let len = self_len + other_len - estimate_len(union_ones, self.m, self.k);
+
+        self.n = len.max(0_f64).round() as u64;
+        Ok(())
+    }
+
+    /// Returns a new filter containing all of the items in both filters. See `union_with`.
+    pub fn union(&self, other: &Self) -> Result<Self, FlitError>
+    where
+        S: Clone,
+    {
+        let mut union = self.clone();
+        union.union_with(other)?;
+        Ok(union)
+    }
+
+    /// Returns a new filter containing the items that might be in both filters. See
+    /// `intersect_with`.
+    pub fn intersect(&self, other: &Self) -> Result<Self, FlitError>
+    where
+        S: Clone,
+    {
+        let mut intersection = self.clone();
+        intersection.intersect_with(other)?;
+        Ok(intersection)
+    }
+
+    fn check_compatible(&self, other: &Self) -> Result<(), FlitError> {
+        if self.m != other.m || self.k != other.k || self.build_hasher != other.build_hasher {
+            return Err(FlitError::IncompatibleFilters);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, S> BloomFilter<T, S> {
+    /// Returns the number of items that have been added to the filter.
+    ///
+    /// Every call to `add` is counted, even if the same item is added more than once. Calls to
+    /// `check_and_add` are only counted if the filter did not already contain the item. See
+    /// `estimated_len` for an estimate of the number of distinct items.
+    pub fn len(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns `true` if no items have been added to the filter.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Estimates the number of distinct items in the filter from the number of bits that are set.
+    ///
+    /// Unlike the number of calls to `add`, this is not inflated by items that were added more
+    /// than once. Returns infinity if every bit in the filter is set.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new(0.01, 10000);
+    /// for _ in 0..100 {
+    ///     filter.add(&"Hello, world!");
+    /// }
+    ///
+    /// assert_eq!(filter.estimated_len().round(), 1.0);
+    /// ```
+    pub fn estimated_len(&self) -> f64 {
+        estimate_len(self.count_ones(), self.m, self.k)
+    }
+
+    /// Returns the fraction of bits in the filter that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.m as f64
+    }
+
+    /// Calculates the current false positive rate from the fraction of bits that are set, rather
+    /// than from the number of items that were added.
+    ///
+    /// This reflects the actual saturation of the filter, even when items have been added more
+    /// than once.
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.k as i32)
+    }
+
+    /// Removes every item from the filter, keeping its bits allocated.
+    pub(crate) fn clear(&mut self) {
+        self.bit_vec.set_all(false);
+        self.n = 0;
+    }
+
+    /// Sets the bits of an item, given its hashes from `split_hash`.
+    fn add_split_hash(&mut self, split_hash: (u64, u64)) {
+        for i in indices_for_hash(split_hash, self.m, self.k) {
+            self.bit_vec.set(i, true);
+        }
+
+        self.n += 1;
+    }
+
+    /// Checks if all of the bits of an item are set, given its hashes from `split_hash`.
+    pub(crate) fn contains_split_hash(&self, split_hash: (u64, u64)) -> bool {
+        indices_for_hash(split_hash, self.m, self.k).all(|i| self.bit_vec[i])
+    }
+
+    fn count_ones(&self) -> u64 {
+        self.bit_vec
+            .as_slice()
+            .iter()
+            .map(|word| u64::from(word.count_ones()))
+            .sum()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> Extend<T> for BloomFilter<T, S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.add(&item);
+        }
+    }
+}
+
+impl<'a, T: 'a + Hash, S: BuildHasher> Extend<&'a T> for BloomFilter<T, S> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, items: I) {
+        for item in items {
+            self.add(item);
+        }
+    }
+}
+
+impl<T, S: Clone> Clone for BloomFilter<T, S> {
+    fn clone(&self) -> Self {
+        BloomFilter {
+            n: self.n,
+            m: self.m,
+            k: self.k,
+            bit_vec: self.bit_vec.clone(),
+            build_hasher: self.build_hasher.clone(),
+            _phantom: PhantomData,
+        }
     }
 }
 
-/// Hashes `item` using a `Hasher`, and produces a two-element tuple.
-/// The first element is the "upper half" of the `u64` produced by the hash function, and the second
-/// element is the "lower half".
-fn split_hash<T: Hash>(item: &T, hasher: &impl BuildHasher) -> (u32, u32) {
-    let mut hasher = hasher.build_hasher();
-    item.hash(&mut hasher);
-    let hash = hasher.finish();
+impl<T, S: Clone + PartialEq> BitOr for &BloomFilter<T, S> {
+    type Output = Result<BloomFilter<T, S>, FlitError>;
 
-    (((hash >> 32) as u32), hash as u32)
+    /// Returns the union of two filters. See `BloomFilter::union`.
+    fn bitor(self, other: Self) -> Self::Output {
+        self.union(other)
+    }
+}
+
+impl<T, S: Clone + PartialEq> BitAnd for &BloomFilter<T, S> {
+    type Output = Result<BloomFilter<T, S>, FlitError>;
+
+    /// Returns the intersection of two filters. See `BloomFilter::intersect`.
+    fn bitand(self, other: Self) -> Self::Output {
+        self.intersect(other)
+    }
+}
+
+/// Calculates the number of bits (`m`) and the number of hashes (`k`) needed for a filter to
+/// hold `estimated_items` items at the given `false_positive_rate`.
+///
+/// Returns an error if `false_positive_rate` is not between 0 and 1 (non inclusive), if
+/// `estimated_items` is not greater than 0, or if the filter would be too large to allocate.
+pub(crate) fn optimal_parameters(
+    false_positive_rate: f64,
+    estimated_items: usize,
+) -> Result<(u64, u32), FlitError> {
+    if !(false_positive_rate > 0_f64 && false_positive_rate < 1_f64) {
+        return Err(FlitError::InvalidFalsePositiveRate(false_positive_rate));
+    }
+    if estimated_items == 0 {
+        return Err(FlitError::ZeroCapacity);
+    }
+
+    let num_bits = -(estimated_items as f64) * false_positive_rate.ln() / LN2_SQUARED;
+    if num_bits.ceil() > MAX_BITS as f64 {
+        return Err(FlitError::CapacityOverflow);
+    }
+
+    let num_hashes = (num_bits / estimated_items as f64) * LN_2;
+
+    Ok((num_bits.ceil() as u64, num_hashes.ceil() as u32))
+}
+
+/// Calculates the number of hashes (`k`) that minimizes the false positive rate of a filter with
+/// `num_bits` bits, once `estimated_items` items have been added to it.
+///
+/// Returns `FlitError::InvalidParameter` if the number of hashes does not fit in a `u32`.
+pub(crate) fn optimal_num_hashes(num_bits: u64, estimated_items: usize) -> Result<u32, FlitError> {
+    let num_hashes = ((num_bits as f64 / estimated_items as f64) * LN_2).ceil();
+    if num_hashes > f64::from(u32::MAX) {
+        return Err(FlitError::InvalidParameter("num_hashes"));
+    }
+
+    Ok((num_hashes as u32).max(1))
+}
+
+/// Calculates the number of items that a filter with `num_bits` bits can hold before its false
+/// positive rate rises above `false_positive_rate`. This is the inverse of `optimal_parameters`.
+///
+/// Returns an error if `false_positive_rate` is not between 0 and 1 (non inclusive).
+pub(crate) fn optimal_num_items(
+    false_positive_rate: f64,
+    num_bits: u64,
+) -> Result<usize, FlitError> {
+    if !(false_positive_rate > 0_f64 && false_positive_rate < 1_f64) {
+        return Err(FlitError::InvalidFalsePositiveRate(false_positive_rate));
+    }
+
+    let estimated_items = -(num_bits as f64) * LN2_SQUARED / false_positive_rate.ln();
+
+    Ok(estimated_items.floor() as usize)
+}
+
+/// Calculates the expected false positive rate of a filter with `m` bits and `k` hashes, after
+/// `n` items have been added to it.
+pub(crate) fn false_positive_rate(n: u64, m: u64, k: u32) -> f64 {
+    (1_f64 - E.powf(-f64::from(k) * n as f64 / m as f64)).powi(k as i32)
+}
+
+/// Estimates the number of distinct items that were added to a filter with `m` bits and `k`
+/// hashes, given that `ones` of its bits are set.
+///
+/// See Swamidass & Baldi, [Mathematical Correction for Fingerprint Similarity
+/// Measures](https://doi.org/10.1021/ci600526a).
+pub(crate) fn estimate_len(ones: u64, m: u64, k: u32) -> f64 {
+    if ones >= m {
+        return f64::INFINITY;
+    }
+
+    -(m as f64 / f64::from(k)) * (1_f64 - ones as f64 / m as f64).ln()
+}
+
+/// Hashes `item` using a `Hasher`, and produces a two-element tuple of 64-bit hashes.
+///
+/// The first element is the `u64` produced by the hash function, and the second element is that
+/// value passed through MurmurHash3's 64-bit finalizer. The finalizer is a bijection with good
+/// avalanche behaviour, so the second hash is well distributed even if the first one is not.
+pub(crate) fn split_hash<T: ?Sized + Hash>(item: &T, hasher: &impl BuildHasher) -> (u64, u64) {
+    let hash = hasher.hash_one(item);
+
+    (hash, fmix64(hash))
+}
+
+/// The 64-bit finalizer of MurmurHash3.
+pub(crate) fn fmix64(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
 }
 
 /// Returns the indices to be set to "true" in a Bloom filter for a given hash.
 ///
-/// `split_hash` is a tuple of two `u32` values produced by passing the item to be added through
+/// `split_hash` is a tuple of two `u64` values produced by passing the item to be added through
 /// the `split_hash` function.
 /// `m` is the number of indices in the filter.
 /// `k` is the number of hash functions that the item should be passed through.
-fn indices_for_hash(split_hash: (u32, u32), m: u64, k: u32) -> impl Iterator<Item = usize> {
-    (0..k).map(move |i| {
-        (u64::from(split_hash.0.wrapping_add(split_hash.1.wrapping_mul(i))) % m) as usize
+///
+/// The indices are derived using enhanced double hashing, which is computed modulo `m` at every
+/// step, so that every index in a filter of any size can be reached. See Dillinger & Manolios,
+/// [Bloom Filters in Probabilistic
+/// Verification](https://www.khoury.northeastern.edu/~pete/pub/bloom-filters-verification.pdf).
+pub(crate) fn indices_for_hash(
+    split_hash: (u64, u64),
+    m: u64,
+    k: u32,
+) -> impl Iterator<Item = usize> {
+    let mut x = split_hash.0 % m;
+    let mut y = split_hash.1 % m;
+
+    (1..=u64::from(k)).map(move |i| {
+        let index = x;
+        x = add_mod(x, y, m);
+        y = add_mod(y, i % m, m);
+        index as usize
     })
 }
 
+/// Calculates `(a + b) % m` for `a, b < m`, without overflowing.
+fn add_mod(a: u64, b: u64, m: u64) -> u64 {
+    if a >= m - b {
+        a - (m - b)
+    } else {
+        a + b
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,6 +1039,70 @@ mod tests {
         assert_eq!(filter.k, 7);
     }
 
+    #[test]
+    fn test_try_new() {
+        assert!(BloomFilter::<&str>::try_new(0.01_f64, 100).is_ok());
+
+        assert!(matches!(
+            BloomFilter::<&str>::try_new(0_f64, 100),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            BloomFilter::<&str>::try_new(1_f64, 100),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            BloomFilter::<&str>::try_new(f64::NAN, 100),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            BloomFilter::<&str>::try_new(0.01_f64, 0),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(matches!(
+            BloomFilter::<&str>::try_new(1e-300_f64, usize::MAX),
+            Err(FlitError::CapacityOverflow)
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_new_panics() {
+        BloomFilter::<&str>::new(0.01_f64, 0);
+    }
+
+    #[test]
+    fn test_indices_cover_large_filters() {
+        const BUCKETS: usize = 16;
+        const ITEMS: u64 = 100_000;
+
+        let m = 1_u64 << 40;
+        let k = 7;
+        let build_hasher = XxHashBuilder::with_seed(42);
+        let mut buckets = [0_u64; BUCKETS];
+
+        for item in 0..ITEMS {
+            for i in indices_for_hash(split_hash(&item, &build_hasher), m, k) {
+                assert!((i as u64) < m);
+                buckets[(i as u64 / (m / BUCKETS as u64)) as usize] += 1;
+            }
+        }
+
+        // Every bucket should receive close to an equal share of the indices. With 700,000
+        // indices, a deviation of 3% is more than 10 standard deviations.
+        let expected = ITEMS * u64::from(k) / BUCKETS as u64;
+        for &count in buckets.iter() {
+            assert!(count > expected * 97 / 100 && count < expected * 103 / 100);
+        }
+    }
+
+    #[test]
+    fn test_add_mod() {
+        assert_eq!(add_mod(3, 4, 5), 2);
+        assert_eq!(add_mod(u64::MAX - 1, u64::MAX - 1, u64::MAX), u64::MAX - 2);
+        assert_eq!(add_mod(0, 0, 1), 0);
+    }
+
     #[test]
     fn test_false_positive_rate_empty() {
         let filter = BloomFilter::<&str>::new(0.01_f64, 216553);
@@ -170,7 +1118,361 @@ mod tests {
         filter.add(&"Hello, world!");
 
         assert!(filter.false_positive_rate() > 0.0);
-        assert_eq!(filter.might_contain(&"Hello, world!"), true);
-        assert_eq!(filter.might_contain(&"Dogs are cool!"), false);
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(!filter.might_contain(&"Dogs are cool!"));
+    }
+
+    #[test]
+    fn test_borrowed_strings() {
+        let mut owned = BloomFilter::<String>::with_seed(0.01_f64, 100, 42);
+        let mut borrowed = BloomFilter::<&str>::with_seed(0.01_f64, 100, 42);
+
+        owned.add_borrowed("Hello, world!");
+        owned.add(&"Dogs are cool!".to_string());
+        borrowed.add(&"Hello, world!");
+        borrowed.add(&"Dogs are cool!");
+
+        assert_eq!(owned.bit_vec, borrowed.bit_vec);
+        assert!(owned.might_contain("Dogs are cool!"));
+        assert!(owned.might_contain(&"Hello, world!".to_string()));
+        assert!(borrowed.might_contain("Hello, world!"));
+    }
+
+    #[test]
+    fn test_borrowed_bytes() {
+        let mut owned = BloomFilter::<Vec<u8>>::with_seed(0.01_f64, 100, 42);
+        let mut borrowed = BloomFilter::<&[u8]>::with_seed(0.01_f64, 100, 42);
+
+        owned.add_borrowed(&b"Hello, world!"[..]);
+        owned.add(&b"Dogs are cool!".to_vec());
+        borrowed.add(&&b"Hello, world!"[..]);
+        borrowed.add(&&b"Dogs are cool!"[..]);
+
+        assert_eq!(owned.bit_vec, borrowed.bit_vec);
+        assert!(owned.might_contain(&b"Dogs are cool!"[..]));
+        assert!(owned.might_contain(&b"Hello, world!".to_vec()));
+        assert!(borrowed.might_contain(&b"Hello, world!"[..]));
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut owned = BloomFilter::with_seed(0.01_f64, 1000, 42);
+        let mut borrowed = BloomFilter::<u32>::with_seed(0.01_f64, 1000, 42);
+        let items: Vec<u32> = (0..500).collect();
+
+        owned.extend(items.clone());
+        borrowed.extend(&items);
+
+        assert_eq!(owned.n, 500);
+        assert_eq!(owned.bit_vec, borrowed.bit_vec);
+        assert!(items.iter().all(|i| owned.might_contain(i)));
+    }
+
+    #[test]
+    fn test_from_iter_with_rate() {
+        let filter = BloomFilter::from_iter_with_rate(0.01_f64, 0..1000);
+
+        assert_eq!(filter.len(), 1000);
+        assert_eq!(
+            filter.num_bits(),
+            optimal_parameters(0.01_f64, 1000).unwrap().0
+        );
+        assert!((0..1000).all(|i| filter.might_contain(&i)));
+
+        assert!(matches!(
+            BloomFilter::try_from_iter_with_rate(0.01_f64, Vec::<u32>::new()),
+            Err(FlitError::ZeroCapacity)
+        ));
+
+        // Iterators without an exact length are counted first.
+        let filter = BloomFilter::from_iter_with_rate(0.01_f64, (0..2000).filter(|i| i % 2 == 0));
+
+        assert_eq!(filter.len(), 1000);
+        assert_eq!(
+            filter.num_bits(),
+            optimal_parameters(0.01_f64, 1000).unwrap().0
+        );
+        assert!((0..2000).step_by(2).all(|i| filter.might_contain(&i)));
+
+        assert!(matches!(
+            BloomFilter::try_from_iter_with_rate(0.01_f64, (0..10_u32).filter(|_| false)),
+            Err(FlitError::ZeroCapacity)
+        ));
+    }
+
+    #[test]
+    fn test_check_and_add() {
+        let mut checked = BloomFilter::with_seed(0.01_f64, 1000, 42);
+        let mut added = BloomFilter::with_seed(0.01_f64, 1000, 42);
+
+        for i in 0..500 {
+            assert_eq!(checked.check_and_add(&i), added.might_contain(&i));
+            added.add(&i);
+        }
+        assert_eq!(checked.bit_vec, added.bit_vec);
+
+        let len = checked.len();
+        let false_positive_rate = checked.false_positive_rate();
+        for i in 0..500 {
+            assert!(checked.check_and_add(&i));
+        }
+
+        assert!(len <= 500);
+        assert_eq!(checked.len(), len);
+        assert_eq!(checked.false_positive_rate(), false_positive_rate);
+        assert_eq!(checked.bit_vec, added.bit_vec);
+    }
+
+    #[test]
+    fn test_with_seed() {
+        let mut a = BloomFilter::with_seed(0.01_f64, 100, 42);
+        let mut b = BloomFilter::with_seed(0.01_f64, 100, 42);
+
+        a.add(&"Hello, world!");
+        b.add(&"Hello, world!");
+
+        assert_eq!(a.seed(), 42);
+        assert_eq!(a.bit_vec, b.bit_vec);
+    }
+
+    #[test]
+    fn test_serialization_round_trip() {
+        let mut filter = BloomFilter::with_seed(0.01_f64, 100, 42);
+        filter.add(&"Hello, world!");
+
+        let bytes = filter.to_bytes();
+        let restored = BloomFilter::<&str>::from_bytes(&bytes).unwrap();
+
+        assert_eq!(restored.seed(), 42);
+        assert_eq!(restored.n, filter.n);
+        assert_eq!(restored.m, filter.m);
+        assert_eq!(restored.k, filter.k);
+        assert_eq!(restored.bit_vec, filter.bit_vec);
+        assert!(restored.might_contain(&"Hello, world!"));
+
+        let mut written = Vec::new();
+        filter.write_to(&mut written).unwrap();
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn test_deserialization_errors() {
+        let mut filter = BloomFilter::with_seed(0.01_f64, 100, 42);
+        filter.add(&"Hello, world!");
+        let bytes = filter.to_bytes();
+
+        let mut corrupted = bytes.clone();
+        corrupted[40] ^= 1;
+        assert!(matches!(
+            BloomFilter::<&str>::from_bytes(&corrupted),
+            Err(DecodeError::ChecksumMismatch)
+        ));
+
+        let mut bad_magic = bytes.clone();
+        bad_magic[0] = b'X';
+        assert!(matches!(
+            BloomFilter::<&str>::from_bytes(&bad_magic),
+            Err(DecodeError::InvalidMagic)
+        ));
+
+        let mut bad_version = bytes.clone();
+        bad_version[4] = 99;
+        assert!(matches!(
+            BloomFilter::<&str>::from_bytes(&bad_version),
+            Err(DecodeError::UnsupportedVersion(99))
+        ));
+
+        let mut bad_hasher = bytes.clone();
+        bad_hasher[5] = 99;
+        assert!(matches!(
+            BloomFilter::<&str>::from_bytes(&bad_hasher),
+            Err(DecodeError::UnsupportedHasher(99))
+        ));
+
+        assert!(matches!(
+            BloomFilter::<&str>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::Io(_))
+        ));
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(matches!(
+            BloomFilter::<&str>::from_bytes(&trailing),
+            Err(DecodeError::TrailingBytes)
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let mut filter = BloomFilter::with_seed(0.01_f64, 100, 42);
+        filter.add(&"Hello, world!");
+
+        let json = serde_json::to_string(&filter).unwrap();
+        let restored: BloomFilter<&str> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.seed(), 42);
+        assert_eq!(restored.n, filter.n);
+        assert_eq!(restored.bit_vec, filter.bit_vec);
+        assert!(restored.might_contain(&"Hello, world!"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_validation() {
+        let json = r#"{"seed":42,"n":0,"m":100,"k":0,"bits":[0,0]}"#;
+        assert!(serde_json::from_str::<BloomFilter<&str>>(json).is_err());
+
+        let json = r#"{"seed":42,"n":0,"m":100,"k":7,"bits":[0]}"#;
+        assert!(serde_json::from_str::<BloomFilter<&str>>(json).is_err());
+
+        let json = r#"{"seed":42,"n":0,"m":100,"k":7,"bits":[0,0]}"#;
+        assert!(serde_json::from_str::<BloomFilter<&str>>(json).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_par_extend_matches_sequential() {
+        use rayon::iter::IntoParallelRefIterator;
+
+        let items: Vec<u64> = (0..100_000).collect();
+        let mut sequential = BloomFilter::with_seed(0.01_f64, 100_000, 42);
+        let mut parallel = BloomFilter::with_seed(0.01_f64, 100_000, 42);
+        let mut borrowed = BloomFilter::with_seed(0.01_f64, 100_000, 42);
+
+        sequential.add(&u64::MAX);
+        parallel.add(&u64::MAX);
+        borrowed.add(&u64::MAX);
+        for item in &items {
+            sequential.add(item);
+        }
+        parallel.par_extend(items.clone());
+        borrowed.par_extend(items.par_iter());
+
+        assert_eq!(parallel.n, sequential.n);
+        assert_eq!(parallel.bit_vec, sequential.bit_vec);
+        assert_eq!(borrowed.n, sequential.n);
+        assert_eq!(borrowed.bit_vec, sequential.bit_vec);
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn test_from_par_iter() {
+        let filter = BloomFilter::from_par_iter(0.01_f64, 0..10_000_u32);
+
+        assert_eq!(filter.len(), 10_000);
+        assert_eq!(
+            filter.num_bits(),
+            optimal_parameters(0.01_f64, 10_000).unwrap().0
+        );
+        assert!((0..10_000).all(|i| filter.might_contain(&i)));
+
+        assert!(matches!(
+            BloomFilter::try_from_par_iter(0.01_f64, 0..0_u32),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(matches!(
+            BloomFilter::try_from_par_iter(1.5_f64, 0..10_u32),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+    }
+
+    #[test]
+    fn test_estimated_len() {
+        let mut filter = BloomFilter::new(0.01_f64, 10000);
+
+        assert_eq!(filter.estimated_len(), 0_f64);
+        assert_eq!(filter.estimated_false_positive_rate(), 0_f64);
+
+        for i in 0..5000 {
+            filter.add(&i);
+            filter.add(&i);
+        }
+
+        assert_eq!(filter.n, 10000);
+        assert!((4900_f64..5100_f64).contains(&filter.estimated_len()));
+        assert!(filter.estimated_false_positive_rate() < filter.false_positive_rate());
+    }
+
+    #[test]
+    fn test_estimated_len_saturated() {
+        let mut filter = BloomFilter::new(0.5_f64, 1);
+
+        for i in 0..100 {
+            filter.add(&i);
+        }
+
+        assert_eq!(filter.fill_ratio(), 1_f64);
+        assert_eq!(filter.estimated_len(), f64::INFINITY);
+        assert_eq!(filter.estimated_false_positive_rate(), 1_f64);
+    }
+
+    #[test]
+    fn test_union() {
+        let mut a = BloomFilter::with_seed(0.01_f64, 1000, 42);
+        let mut b = BloomFilter::with_seed(0.01_f64, 1000, 42);
+
+        for i in 0..300 {
+            a.add(&i);
+        }
+        for i in 200..500 {
+            b.add(&i);
+        }
+
+        let union = (&a | &b).unwrap();
+
+        assert!((0..500).all(|i| union.might_contain(&i)));
+        assert!((480..520).contains(&union.n));
+        assert!(union.false_positive_rate() < 0.01);
+
+        a.union_with(&b).unwrap();
+        assert_eq!(a.bit_vec, union.bit_vec);
+    }
+
+    #[test]
+    fn test_intersect() {
+        let mut a = BloomFilter::with_seed(0.01_f64, 1000, 42);
+        let mut b = BloomFilter::with_seed(0.01_f64, 1000, 42);
+
+        for i in 0..300 {
+            a.add(&i);
+        }
+        for i in 200..500 {
+            b.add(&i);
+        }
+
+        let intersection = (&a & &b).unwrap();
+
+        assert!((200..300).all(|i| intersection.might_contain(&i)));
+        assert!((80..120).contains(&intersection.n));
+    }
+
+    #[test]
+    fn test_merge_incompatible() {
+        let a = BloomFilter::<u32>::with_seed(0.01_f64, 1000, 42);
+
+        let different_seed = BloomFilter::with_seed(0.01_f64, 1000, 43);
+        assert!(matches!(
+            a.union(&different_seed),
+            Err(FlitError::IncompatibleFilters)
+        ));
+
+        let different_size = BloomFilter::with_seed(0.01_f64, 2000, 42);
+        assert!(matches!(
+            a.intersect(&different_size),
+            Err(FlitError::IncompatibleFilters)
+        ));
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        use std::collections::hash_map::RandomState;
+
+        let mut filter = BloomFilter::with_hasher(0.03_f64, 10, RandomState::new());
+
+        filter.add(&"Hello, world!");
+
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(!filter.might_contain(&"Dogs are cool!"));
     }
 }
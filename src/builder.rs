@@ -0,0 +1,455 @@
+//! `BloomFilterBuilder` creates a [`BloomFilter`] from any consistent combination of sizing
+//! parameters, rather than only from a false-positive rate and an estimated number of items.
+//!
+//! The size of the filter (`m`) is determined by exactly one of:
+//!
+//! - a false-positive rate and an estimated number of items, using the same math as
+//!   `BloomFilter::new`,
+//! - a memory budget in bytes,
+//! - a number of bits per item, together with an estimated number of items, or
+//! - an explicit number of bits.
+//!
+//! A memory budget may also be combined with any of the other parameters, in which case it acts as
+//! an upper bound on the size of the filter. The one exception is a false-positive rate without an
+//! estimated number of items: the filter then uses the whole budget, and holds as many items as
+//! it can at that rate.
+//!
+//! The number of hashes (`k`) can either be set explicitly, or is calculated from `m` and the
+//! estimated number of items. It cannot be set together with a false-positive rate, since the rate
+//! already determines the number of hashes.
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+
+use crate::bloom_filter::{optimal_num_hashes, optimal_num_items, optimal_parameters, BloomFilter};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use std::hash::{BuildHasher, Hash};
+
+/// Builds a [`BloomFilter`] from a combination of sizing parameters.
+///
+/// # Example
+/// ```rust
+/// use flit::{BloomFilter, BloomFilterBuilder};
+///
+/// // Use at most 64 MiB, and pick the best number of hashes for 10 million items.
+/// let mut filter: BloomFilter<u64> = BloomFilterBuilder::new()
+///     .max_bytes(64 * 1024 * 1024)
+///     .estimated_items(10_000_000)
+///     .build()
+///     .unwrap();
+///
+/// filter.add(&42);
+/// assert_eq!(filter.might_contain(&42), true);
+/// ```
+///
+/// [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+#[derive(Clone, Debug)]
+pub struct BloomFilterBuilder<S = XxHashBuilder> {
+    false_positive_rate: Option<f64>,
+    estimated_items: Option<usize>,
+    max_bytes: Option<u64>,
+    bits_per_item: Option<f64>,
+    num_bits: Option<u64>,
+    num_hashes: Option<u32>,
+    build_hasher: S,
+}
+
+impl BloomFilterBuilder {
+    /// Creates a new builder with no parameters set, which hashes items using a random seed.
+    pub fn new() -> Self {
+        Self::with_hasher(XxHashBuilder::random())
+    }
+
+    /// Hashes items using the given `seed`. See `BloomFilter::with_seed`.
+    pub fn seed(self, seed: u64) -> Self {
+        self.hasher(XxHashBuilder::with_seed(seed))
+    }
+}
+
+impl Default for BloomFilterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> BloomFilterBuilder<S> {
+    /// Creates a new builder with no parameters set, which hashes items using `build_hasher`.
+    pub fn with_hasher(build_hasher: S) -> Self {
+        BloomFilterBuilder {
+            false_positive_rate: None,
+            estimated_items: None,
+            max_bytes: None,
+            bits_per_item: None,
+            num_bits: None,
+            num_hashes: None,
+            build_hasher,
+        }
+    }
+
+    /// Hashes items using `build_hasher`. See `BloomFilter::with_hasher`.
+    pub fn hasher<H>(self, build_hasher: H) -> BloomFilterBuilder<H> {
+        BloomFilterBuilder {
+            false_positive_rate: self.false_positive_rate,
+            estimated_items: self.estimated_items,
+            max_bytes: self.max_bytes,
+            bits_per_item: self.bits_per_item,
+            num_bits: self.num_bits,
+            num_hashes: self.num_hashes,
+            build_hasher,
+        }
+    }
+
+    /// Sets the desired false-positive rate once `estimated_items` items have been added.
+    pub fn false_positive_rate(mut self, false_positive_rate: f64) -> Self {
+        self.false_positive_rate = Some(false_positive_rate);
+        self
+    }
+
+    /// Sets the number of items that are expected to be added to the filter.
+    pub fn estimated_items(mut self, estimated_items: usize) -> Self {
+        self.estimated_items = Some(estimated_items);
+        self
+    }
+
+    /// Limits the bits of the filter to at most `max_bytes` bytes.
+    ///
+    /// If no other parameter determines the size of the filter, or a false-positive rate is set
+    /// without an estimated number of items, the filter uses the entire budget.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Sets the number of bits used for each of the `estimated_items` items.
+    pub fn bits_per_item(mut self, bits_per_item: f64) -> Self {
+        self.bits_per_item = Some(bits_per_item);
+        self
+    }
+
+    /// Sets the exact number of bits in the filter (`m`).
+    pub fn num_bits(mut self, num_bits: u64) -> Self {
+        self.num_bits = Some(num_bits);
+        self
+    }
+
+    /// Sets the exact number of hashes applied to each item (`k`).
+    ///
+    /// This cannot be combined with `false_positive_rate`.
+    pub fn num_hashes(mut self, num_hashes: u32) -> Self {
+        self.num_hashes = Some(num_hashes);
+        self
+    }
+
+    /// Builds the filter, or returns an error if the parameters are missing, invalid or
+    /// contradict each other.
+    pub fn build<T: Hash>(self) -> Result<BloomFilter<T, S>, FlitError>
+    where
+        S: BuildHasher,
+    {
+        let (num_bits, num_hashes) = self.parameters()?;
+
+        BloomFilter::from_parameters(num_bits, num_hashes, self.build_hasher)
+    }
+
+    /// Works out `m` and `k` from the parameters that were provided.
+    fn parameters(&self) -> Result<(u64, u32), FlitError> {
+        if let Some(0) = self.estimated_items {
+            return Err(FlitError::ZeroCapacity);
+        }
+
+        let sources = [
+            ("false_positive_rate", self.false_positive_rate.is_some()),
+            ("bits_per_item", self.bits_per_item.is_some()),
+            ("num_bits", self.num_bits.is_some()),
+        ];
+        let mut given = sources
+            .iter()
+            .filter(|(_, set)| *set)
+            .map(|(name, _)| *name);
+        if let (Some(first), Some(second)) = (given.next(), given.next()) {
+            return Err(FlitError::ConflictingParameters(first, second));
+        }
+
+        // The false positive rate determines the number of hashes as well as the size.
+        if self.false_positive_rate.is_some() && self.num_hashes.is_some() {
+            return Err(FlitError::ConflictingParameters(
+                "false_positive_rate",
+                "num_hashes",
+            ));
+        }
+
+        if let Some(0) = self.max_bytes {
+            return Err(FlitError::InvalidParameter("max_bytes"));
+        }
+
+        let (num_bits, optimal_hashes, sized_by) = if let Some(false_positive_rate) =
+            self.false_positive_rate
+        {
+            match (self.estimated_items, self.max_bytes) {
+                (None, Some(max_bytes)) => {
+                    // Without an estimate, use the whole budget, and size the filter for as many
+                    // items as it can hold at the requested rate.
+                    let num_bits = max_bytes
+                        .checked_mul(8)
+                        .ok_or(FlitError::CapacityOverflow)?;
+                    let estimated_items = optimal_num_items(false_positive_rate, num_bits)?;
+                    if estimated_items == 0 {
+                        return Err(FlitError::ConflictingParameters(
+                            "false_positive_rate",
+                            "max_bytes",
+                        ));
+                    }
+                    let (_, num_hashes) = optimal_parameters(false_positive_rate, estimated_items)?;
+
+                    (num_bits, Some(num_hashes), "max_bytes")
+                }
+                _ => {
+                    let estimated_items = self.require_estimated_items()?;
+                    let (num_bits, num_hashes) =
+                        optimal_parameters(false_positive_rate, estimated_items)?;
+
+                    (num_bits, Some(num_hashes), "false_positive_rate")
+                }
+            }
+        } else if let Some(bits_per_item) = self.bits_per_item {
+            if !bits_per_item.is_finite() || bits_per_item <= 0_f64 {
+                return Err(FlitError::InvalidParameter("bits_per_item"));
+            }
+
+            let num_bits = (bits_per_item * self.require_estimated_items()? as f64).ceil();
+            if num_bits >= u64::MAX as f64 {
+                return Err(FlitError::CapacityOverflow);
+            }
+
+            (num_bits as u64, None, "bits_per_item")
+        } else if let Some(num_bits) = self.num_bits {
+            (num_bits, None, "num_bits")
+        } else if let Some(max_bytes) = self.max_bytes {
+            let num_bits = max_bytes
+                .checked_mul(8)
+                .ok_or(FlitError::CapacityOverflow)?;
+
+            (num_bits, None, "max_bytes")
+        } else {
+            return Err(FlitError::MissingParameter("num_bits"));
+        };
+
+        if num_bits == 0 {
+            return Err(FlitError::InvalidParameter("num_bits"));
+        }
+
+        // A budget is only an upper bound when the size is determined by another parameter.
+        if let Some(max_bytes) = self.max_bytes {
+            if num_bits.div_ceil(8) > max_bytes {
+                return Err(FlitError::ConflictingParameters(sized_by, "max_bytes"));
+            }
+        }
+
+        let num_hashes = match (self.num_hashes, optimal_hashes) {
+            (Some(0), _) => return Err(FlitError::InvalidParameter("num_hashes")),
+            (Some(num_hashes), _) | (None, Some(num_hashes)) => num_hashes,
+            (None, None) => optimal_num_hashes(num_bits, self.require_estimated_items()?)?,
+        };
+
+        Ok((num_bits, num_hashes))
+    }
+
+    fn require_estimated_items(&self) -> Result<usize, FlitError> {
+        self.estimated_items
+            .ok_or(FlitError::MissingParameter("estimated_items"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(builder: BloomFilterBuilder) -> Result<BloomFilter<&'static str>, FlitError> {
+        builder.build()
+    }
+
+    #[test]
+    fn test_false_positive_rate_matches_new() {
+        let filter = build(
+            BloomFilterBuilder::new()
+                .false_positive_rate(0.01_f64)
+                .estimated_items(216553),
+        )
+        .unwrap();
+        let expected = BloomFilter::<&str>::new(0.01_f64, 216553);
+
+        assert_eq!(filter.num_bits(), expected.num_bits());
+        assert_eq!(filter.num_hashes(), expected.num_hashes());
+    }
+
+    #[test]
+    fn test_memory_budget() {
+        let filter = build(
+            BloomFilterBuilder::new()
+                .max_bytes(1024)
+                .estimated_items(1000),
+        )
+        .unwrap();
+
+        assert_eq!(filter.num_bits(), 8192);
+        assert_eq!(filter.num_hashes(), 6);
+
+        let result = build(
+            BloomFilterBuilder::new()
+                .false_positive_rate(0.01_f64)
+                .estimated_items(1000)
+                .max_bytes(1024),
+        );
+        assert!(matches!(
+            result,
+            Err(FlitError::ConflictingParameters(
+                "false_positive_rate",
+                "max_bytes"
+            ))
+        ));
+
+        let filter = build(
+            BloomFilterBuilder::new()
+                .false_positive_rate(0.01_f64)
+                .max_bytes(1024),
+        )
+        .unwrap();
+        assert_eq!(filter.num_bits(), 8192);
+        assert_eq!(filter.num_hashes(), 7);
+
+        let result = build(
+            BloomFilterBuilder::new()
+                .false_positive_rate(1e-300_f64)
+                .max_bytes(1),
+        );
+        assert!(matches!(
+            result,
+            Err(FlitError::ConflictingParameters(
+                "false_positive_rate",
+                "max_bytes"
+            ))
+        ));
+
+        let filter = build(
+            BloomFilterBuilder::new()
+                .num_bits(8000)
+                .estimated_items(1000)
+                .max_bytes(1024),
+        )
+        .unwrap();
+        assert_eq!(filter.num_bits(), 8000);
+
+        let filter = build(
+            BloomFilterBuilder::new()
+                .bits_per_item(8_f64)
+                .estimated_items(1000)
+                .max_bytes(1000),
+        )
+        .unwrap();
+        assert_eq!(filter.num_bits(), 8000);
+
+        let result = build(
+            BloomFilterBuilder::new()
+                .bits_per_item(10_f64)
+                .estimated_items(1000)
+                .max_bytes(1000),
+        );
+        assert!(matches!(
+            result,
+            Err(FlitError::ConflictingParameters(
+                "bits_per_item",
+                "max_bytes"
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_bits_per_item() {
+        let filter = build(
+            BloomFilterBuilder::new()
+                .bits_per_item(10_f64)
+                .estimated_items(1000),
+        )
+        .unwrap();
+
+        assert_eq!(filter.num_bits(), 10_000);
+        assert_eq!(filter.num_hashes(), 7);
+    }
+
+    #[test]
+    fn test_explicit_parameters() {
+        let mut filter = build(
+            BloomFilterBuilder::new()
+                .num_bits(1000)
+                .num_hashes(3)
+                .seed(42),
+        )
+        .unwrap();
+
+        filter.add(&"Hello, world!");
+
+        assert_eq!(filter.num_bits(), 1000);
+        assert_eq!(filter.num_hashes(), 3);
+        assert_eq!(filter.seed(), 42);
+        assert!(filter.might_contain(&"Hello, world!"));
+    }
+
+    #[test]
+    fn test_invalid_combinations() {
+        assert!(matches!(
+            build(BloomFilterBuilder::new()),
+            Err(FlitError::MissingParameter("num_bits"))
+        ));
+        assert!(matches!(
+            build(BloomFilterBuilder::new().num_bits(1000)),
+            Err(FlitError::MissingParameter("estimated_items"))
+        ));
+        assert!(matches!(
+            build(
+                BloomFilterBuilder::new()
+                    .num_bits(1000)
+                    .bits_per_item(8_f64)
+            ),
+            Err(FlitError::ConflictingParameters(
+                "bits_per_item",
+                "num_bits"
+            ))
+        ));
+        assert!(matches!(
+            build(BloomFilterBuilder::new().num_bits(0).num_hashes(3)),
+            Err(FlitError::InvalidParameter("num_bits"))
+        ));
+        assert!(matches!(
+            build(BloomFilterBuilder::new().num_bits(1000).num_hashes(0)),
+            Err(FlitError::InvalidParameter("num_hashes"))
+        ));
+        assert!(matches!(
+            build(
+                BloomFilterBuilder::new()
+                    .num_bits(1 << 40)
+                    .estimated_items(1)
+            ),
+            Err(FlitError::InvalidParameter("num_hashes"))
+        ));
+        assert!(matches!(
+            build(BloomFilterBuilder::new().max_bytes(0).estimated_items(10)),
+            Err(FlitError::InvalidParameter("max_bytes"))
+        ));
+        assert!(matches!(
+            build(
+                BloomFilterBuilder::new()
+                    .false_positive_rate(0.01_f64)
+                    .estimated_items(1000)
+                    .num_hashes(3)
+            ),
+            Err(FlitError::ConflictingParameters(
+                "false_positive_rate",
+                "num_hashes"
+            ))
+        ));
+        assert!(matches!(
+            build(BloomFilterBuilder::new().num_bits(1000).estimated_items(0)),
+            Err(FlitError::ZeroCapacity)
+        ));
+    }
+}
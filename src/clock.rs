@@ -0,0 +1,76 @@
+//! Sources of the current time, for filters that forget items after a while.
+//!
+//! [`SystemClock`] reads the monotonic system clock, and is used by default. [`ManualClock`] only
+//! moves when it is advanced explicitly, so that tests can control the passage of time.
+//!
+//! [`ManualClock`]: struct.ManualClock.html
+//! [`SystemClock`]: struct.SystemClock.html
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A source of the current time.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// A clock that reads the monotonic system clock, using `Instant::now`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves forward when `advance` is called.
+///
+/// Clones of a `ManualClock` share the same time, so a clone can be given to a filter while the
+/// original is used to advance it.
+///
+/// # Example
+/// ```rust
+/// use flit::clock::{Clock, ManualClock};
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+///
+/// clock.clone().advance(Duration::from_secs(60));
+///
+/// assert_eq!(clock.now() - start, Duration::from_secs(60));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    /// Creates a new clock, starting at the current system time.
+    pub fn new() -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Moves the clock, and all of its clones, forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().expect("clock lock should not be poisoned");
+        *now += duration;
+    }
+}
+
+impl Default for ManualClock {
+    /// Creates a new clock, starting at the current system time.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().expect("clock lock should not be poisoned")
+    }
+}
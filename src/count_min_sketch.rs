@@ -0,0 +1,408 @@
+//! `CountMinSketch` estimates how many times each item has been added to it, using a fixed
+//! amount of memory no matter how many distinct items there are.
+//!
+//! The sketch is a table of counters with `depth` rows of `width` counters each. Adding an item
+//! increments one counter in every row, chosen by hashing the item, and the estimated count of
+//! an item is the smallest of its counters. Other items that share a counter can only make it
+//! larger, so estimates are never lower than the true count, and with probability `1 - delta`
+//! they are at most `epsilon * total` higher, where `total` is the sum of all counts.
+//!
+//! # References
+//! - [An Improved Data Stream Summary: The Count-Min Sketch and its
+//!   Applications](http://dimacs.rutgers.edu/~graham/pubs/papers/cm-full.pdf)
+//! - [New Directions in Traffic Measurement and
+//!   Accounting](https://cseweb.ucsd.edu/~varghese/PAPERS/sigcomm02.pdf), which describes
+//!   conservative updates.
+
+use crate::bloom_filter::{indices_for_hash, split_hash};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use std::f64::consts::E;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::mem;
+
+/// Represents a Count-Min sketch.
+///
+/// When constructing the sketch using `new`, you need to specify the error factor `epsilon` and
+/// the probability `delta` that an estimate exceeds the error bound. Each estimate is at most
+/// `epsilon` times the sum of all counts higher than the true count, with probability `1 -
+/// delta`.
+///
+/// # Example
+/// ```rust
+/// use flit::CountMinSketch;
+///
+/// let mut sketch = CountMinSketch::new(0.001, 0.01);
+///
+/// sketch.increment(&"Hello, world!");
+/// sketch.increment(&"Hello, world!");
+/// sketch.increment(&"Dogs are cool!");
+///
+/// assert_eq!(sketch.estimate(&"Hello, world!"), 2); // probably exactly 2, never less
+/// assert_eq!(sketch.estimate(&"Cats are cool!"), 0); // probably exactly 0
+/// ```
+pub struct CountMinSketch<T, S = XxHashBuilder> {
+    width: u64,
+    depth: u32,
+    total: u64,
+    conservative: bool,
+    counters: Vec<u64>,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Hash> CountMinSketch<T> {
+    /// Creates a new Count-Min sketch based on the error factor `epsilon` and the probability
+    /// `delta` of exceeding the error bound.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `epsilon` or `delta` is not between 0 and 1 (non inclusive),
+    /// or if the sketch would be too large to allocate.
+    pub fn new(epsilon: f64, delta: f64) -> Self {
+        Self::with_hasher(epsilon, delta, XxHashBuilder::random())
+    }
+
+    /// Creates a new Count-Min sketch like `new`, but hashes items using the given `seed`.
+    ///
+    /// Sketches created with the same parameters and seed can be merged.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `epsilon` or `delta` is not between 0 and 1 (non inclusive),
+    /// or if the sketch would be too large to allocate.
+    pub fn with_seed(epsilon: f64, delta: f64, seed: u64) -> Self {
+        Self::with_hasher(epsilon, delta, XxHashBuilder::with_seed(seed))
+    }
+
+    /// Creates a new Count-Min sketch like `new`, but returns an error instead of panicking if
+    /// the parameters are invalid.
+    pub fn try_new(epsilon: f64, delta: f64) -> Result<Self, FlitError> {
+        Self::try_with_hasher(epsilon, delta, XxHashBuilder::random())
+    }
+
+    /// Creates a new Count-Min sketch like `with_seed`, but returns an error instead of panicking
+    /// if the parameters are invalid.
+    pub fn try_with_seed(epsilon: f64, delta: f64, seed: u64) -> Result<Self, FlitError> {
+        Self::try_with_hasher(epsilon, delta, XxHashBuilder::with_seed(seed))
+    }
+}
+
+impl<T> CountMinSketch<T> {
+    /// Returns the seed used to hash items added to the sketch.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CountMinSketch<T, S> {
+    /// Creates a new Count-Min sketch like `new`, but hashes items using the given
+    /// `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `epsilon` or `delta` is not between 0 and 1 (non inclusive),
+    /// or if the sketch would be too large to allocate. Use `try_with_hasher` to handle these
+    /// cases without panicking.
+    pub fn with_hasher(epsilon: f64, delta: f64, build_hasher: S) -> Self {
+        Self::try_with_hasher(epsilon, delta, build_hasher).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Count-Min sketch like `with_hasher`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_hasher(epsilon: f64, delta: f64, build_hasher: S) -> Result<Self, FlitError> {
+        if !(epsilon > 0_f64 && epsilon < 1_f64) {
+            return Err(FlitError::InvalidParameter("epsilon"));
+        }
+        if !(delta > 0_f64 && delta < 1_f64) {
+            return Err(FlitError::InvalidParameter("delta"));
+        }
+
+        let width = (E / epsilon).ceil();
+        let depth = (1_f64 / delta).ln().ceil().max(1_f64);
+        let num_counters = width * depth;
+        if num_counters > (isize::MAX as usize / mem::size_of::<u64>()) as f64 {
+            return Err(FlitError::CapacityOverflow);
+        }
+
+        Ok(CountMinSketch {
+            width: width as u64,
+            depth: depth as u32,
+            total: 0,
+            conservative: false,
+            counters: vec![0; num_counters as usize],
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Enables conservative updates, which only increment the counters of an item that are
+    /// smaller than its new estimated count.
+    ///
+    /// Conservative updates make estimates more accurate, and they are still never lower than the
+    /// true count. Merged sketches are less accurate than a single sketch that all of the items
+    /// were added to, though.
+    pub fn conservative_update(mut self) -> Self {
+        self.conservative = true;
+        self
+    }
+
+    /// Returns a reference to the sketch's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Increments the count of the `item` by one.
+    pub fn increment(&mut self, item: &T) {
+        self.increment_by(item, 1);
+    }
+
+    /// Increments the count of the `item` by `count`.
+    ///
+    /// Counters saturate at `u64::MAX` instead of overflowing.
+    pub fn increment_by(&mut self, item: &T, count: u64) {
+        let split = split_hash(item, &self.build_hasher);
+        self.total = self.total.saturating_add(count);
+
+        if self.conservative {
+            let estimate = row_indices(split, self.width, self.depth)
+                .map(|i| self.counters[i])
+                .min();
+            let target = estimate.unwrap_or(0).saturating_add(count);
+            for i in row_indices(split, self.width, self.depth) {
+                self.counters[i] = self.counters[i].max(target);
+            }
+        } else {
+            for i in row_indices(split, self.width, self.depth) {
+                self.counters[i] = self.counters[i].saturating_add(count);
+            }
+        }
+    }
+
+    /// Estimates how many times the `item` has been added to the sketch.
+    ///
+    /// The estimate is never lower than the true count, and is at most `epsilon * len()` higher
+    /// than the true count with probability `1 - delta`.
+    pub fn estimate(&self, item: &T) -> u64 {
+        row_indices(split_hash(item, &self.build_hasher), self.width, self.depth)
+            .map(|i| self.counters[i])
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+impl<T, S: PartialEq> CountMinSketch<T, S> {
+    /// Adds all of the counts in `other` to this sketch.
+    ///
+    /// Returns an error, leaving this sketch unchanged, if the sketches do not have the same
+    /// width, depth and hasher, or if only one of them uses conservative updates.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::CountMinSketch;
+    ///
+    /// let mut a = CountMinSketch::with_seed(0.001, 0.01, 42);
+    /// let mut b = CountMinSketch::with_seed(0.001, 0.01, 42);
+    /// a.increment(&"Hello, world!");
+    /// b.increment(&"Hello, world!");
+    ///
+    /// a.merge_with(&b).unwrap();
+    ///
+    /// assert_eq!(a.estimate(&"Hello, world!"), 2);
+    /// ```
+    pub fn merge_with(&mut self, other: &Self) -> Result<(), FlitError> {
+        if self.width != other.width
+            || self.depth != other.depth
+            || self.conservative != other.conservative
+            || self.build_hasher != other.build_hasher
+        {
+            return Err(FlitError::IncompatibleFilters);
+        }
+
+        for (counter, other) in self.counters.iter_mut().zip(&other.counters) {
+            *counter = counter.saturating_add(*other);
+        }
+        self.total = self.total.saturating_add(other.total);
+
+        Ok(())
+    }
+}
+
+impl<T, S> CountMinSketch<T, S> {
+    /// Returns the sum of all counts that have been added to the sketch.
+    pub fn len(&self) -> u64 {
+        self.total
+    }
+
+    /// Returns `true` if nothing has been added to the sketch.
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Returns the number of counters in each row of the sketch.
+    pub fn width(&self) -> u64 {
+        self.width
+    }
+
+    /// Returns the number of rows in the sketch.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Returns `true` if the sketch uses conservative updates.
+    pub fn is_conservative(&self) -> bool {
+        self.conservative
+    }
+}
+
+impl<T, S: Clone> Clone for CountMinSketch<T, S> {
+    fn clone(&self) -> Self {
+        CountMinSketch {
+            width: self.width,
+            depth: self.depth,
+            total: self.total,
+            conservative: self.conservative,
+            counters: self.counters.clone(),
+            build_hasher: self.build_hasher.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Returns the index of an item's counter in every row of a sketch, given the item's
+/// `split_hash`.
+fn row_indices(split_hash: (u64, u64), width: u64, depth: u32) -> impl Iterator<Item = usize> {
+    indices_for_hash(split_hash, width, depth)
+        .enumerate()
+        .map(move |(row, i)| row * width as usize + i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimensions() {
+        let sketch = CountMinSketch::<u32>::new(0.01_f64, 0.01_f64);
+
+        assert_eq!(sketch.width(), 272);
+        assert_eq!(sketch.depth(), 5);
+        assert!(sketch.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        assert!(matches!(
+            CountMinSketch::<u32>::try_new(0_f64, 0.01_f64),
+            Err(FlitError::InvalidParameter("epsilon"))
+        ));
+        assert!(matches!(
+            CountMinSketch::<u32>::try_new(0.01_f64, 1_f64),
+            Err(FlitError::InvalidParameter("delta"))
+        ));
+        assert!(matches!(
+            CountMinSketch::<u32>::try_new(1e-18_f64, 0.01_f64),
+            Err(FlitError::CapacityOverflow)
+        ));
+    }
+
+    fn check_error_bound(mut sketch: CountMinSketch<u32>) {
+        // Item `i` is added `i % 100 + 1` times.
+        for i in 0..10_000_u32 {
+            sketch.increment_by(&i, u64::from(i % 100 + 1));
+        }
+
+        let bound = (0.001_f64 * sketch.len() as f64) as u64;
+        let mut exceeded = 0;
+        for i in 0..10_000_u32 {
+            let count = u64::from(i % 100 + 1);
+            let estimate = sketch.estimate(&i);
+
+            assert!(estimate >= count);
+            if estimate > count + bound {
+                exceeded += 1;
+            }
+        }
+
+        assert!(exceeded < 100);
+    }
+
+    #[test]
+    fn test_estimates() {
+        check_error_bound(CountMinSketch::with_seed(0.001_f64, 0.01_f64, 42));
+    }
+
+    #[test]
+    fn test_conservative_estimates() {
+        check_error_bound(CountMinSketch::with_seed(0.001_f64, 0.01_f64, 42).conservative_update());
+    }
+
+    #[test]
+    fn test_conservative_is_more_accurate() {
+        let mut standard = CountMinSketch::with_seed(0.01_f64, 0.01_f64, 42);
+        let mut conservative =
+            CountMinSketch::with_seed(0.01_f64, 0.01_f64, 42).conservative_update();
+
+        for i in 0..10_000 {
+            standard.increment(&i);
+            conservative.increment(&i);
+        }
+
+        let error = |sketch: &CountMinSketch<i32>| -> u64 {
+            (0..10_000).map(|i| sketch.estimate(&i) - 1).sum()
+        };
+
+        assert!(error(&conservative) < error(&standard));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = CountMinSketch::with_seed(0.001_f64, 0.01_f64, 42);
+        let mut b = CountMinSketch::with_seed(0.001_f64, 0.01_f64, 42);
+        let mut both = CountMinSketch::with_seed(0.001_f64, 0.01_f64, 42);
+
+        for i in 0..1000 {
+            a.increment(&i);
+            both.increment(&i);
+        }
+        for i in 500..1500 {
+            b.increment(&i);
+            both.increment(&i);
+        }
+
+        a.merge_with(&b).unwrap();
+
+        assert_eq!(a.len(), 2000);
+        assert!((0..1500).all(|i| a.estimate(&i) == both.estimate(&i)));
+    }
+
+    #[test]
+    fn test_merge_incompatible() {
+        let mut a = CountMinSketch::<u32>::with_seed(0.001_f64, 0.01_f64, 42);
+
+        assert!(matches!(
+            a.merge_with(&CountMinSketch::with_seed(0.01_f64, 0.01_f64, 42)),
+            Err(FlitError::IncompatibleFilters)
+        ));
+        assert!(matches!(
+            a.merge_with(&CountMinSketch::with_seed(0.001_f64, 0.01_f64, 7)),
+            Err(FlitError::IncompatibleFilters)
+        ));
+        assert!(matches!(
+            a.merge_with(&CountMinSketch::with_seed(0.001_f64, 0.01_f64, 42).conservative_update()),
+            Err(FlitError::IncompatibleFilters)
+        ));
+    }
+
+    #[test]
+    fn test_counters_saturate() {
+        let mut sketch = CountMinSketch::with_seed(0.01_f64, 0.01_f64, 42);
+
+        sketch.increment_by(&"Hello, world!", u64::MAX);
+        sketch.increment(&"Hello, world!");
+
+        assert_eq!(sketch.estimate(&"Hello, world!"), u64::MAX);
+        assert_eq!(sketch.len(), u64::MAX);
+    }
+}
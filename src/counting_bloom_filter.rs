@@ -0,0 +1,421 @@
+//! `CountingBloomFilter` is a variant of a Bloom filter that replaces each bit with a small
+//! counter. This allows items to be removed from the filter, at the cost of a larger memory
+//! footprint: a filter with 4-bit counters needs four times as much space as an equivalent
+//! [`BloomFilter`].
+//!
+//! Counters saturate at their maximum value instead of overflowing. Once a counter has saturated,
+//! it is never decremented again, since the filter can no longer tell how many items contributed
+//! to it. This keeps the filter free of false negatives, at the cost of a slightly higher
+//! false-positive rate for heavily loaded filters.
+//!
+//! # References
+//! - [Summary Cache: A Scalable Wide-Area Web Cache Sharing
+//!   Protocol](http://pages.cs.wisc.edu/~jussara/papers/00ton.pdf)
+//! - [Wikipedia article](https://en.wikipedia.org/wiki/Counting_Bloom_filter)
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+
+use crate::bloom_filter::{false_positive_rate, indices_for_hash, optimal_parameters, split_hash};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use crate::packed_vec::PackedVec;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// The number of bits used for each counter in a [`CountingBloomFilter`].
+///
+/// [`CountingBloomFilter`]: struct.CountingBloomFilter.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CounterWidth {
+    /// 4-bit counters, saturating at 15. This is enough for almost all use cases.
+    #[default]
+    Four,
+    /// 8-bit counters, saturating at 255.
+    Eight,
+    /// 16-bit counters, saturating at 65535.
+    Sixteen,
+}
+
+impl CounterWidth {
+    fn bits(self) -> u32 {
+        match self {
+            CounterWidth::Four => 4,
+            CounterWidth::Eight => 8,
+            CounterWidth::Sixteen => 16,
+        }
+    }
+}
+
+/// Represents a Counting Bloom filter.
+///
+/// When constructing the filter using `new`, you need to specify the desired acceptable
+/// false-positive rate, and the number of items you intend to store in the filter. Neither can be
+/// adjusted after creation - create a new filter instead.
+///
+/// # Example
+/// ```rust
+/// use flit::CountingBloomFilter;
+///
+/// let mut filter = CountingBloomFilter::new(0.01, 10000);
+/// filter.add(&"Hello, world!");
+///
+/// assert_eq!(filter.might_contain(&"Hello, world!"), true); // probably true
+///
+/// assert_eq!(filter.remove(&"Hello, world!"), true);
+/// assert_eq!(filter.might_contain(&"Hello, world!"), false); // definitely false!
+/// ```
+pub struct CountingBloomFilter<T, S = XxHashBuilder> {
+    n: u64,
+    m: u64,
+    k: u32,
+    counters: PackedVec,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Hash> CountingBloomFilter<T> {
+    /// Creates a new Counting Bloom filter with 4-bit counters, based on the required false
+    /// positive rate and the estimated number of items that will be added to the filter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_new` to handle these cases without panicking.
+    pub fn new(false_positive_rate: f64, estimated_items: usize) -> Self {
+        Self::with_counter_width(
+            false_positive_rate,
+            estimated_items,
+            CounterWidth::default(),
+        )
+    }
+
+    /// Creates a new Counting Bloom filter whose counters are `counter_width` bits wide.
+    ///
+    /// Wider counters are less likely to saturate when the same item is added many times, but
+    /// take up proportionally more space.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate.
+    pub fn with_counter_width(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        counter_width: CounterWidth,
+    ) -> Self {
+        Self::with_counter_width_and_seed(
+            false_positive_rate,
+            estimated_items,
+            counter_width,
+            rand::random(),
+        )
+    }
+
+    /// Creates a new Counting Bloom filter with 4-bit counters, which hashes items using the
+    /// given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_seed` to handle these cases without panicking.
+    pub fn with_seed(false_positive_rate: f64, estimated_items: usize, seed: u64) -> Self {
+        Self::with_counter_width_and_seed(
+            false_positive_rate,
+            estimated_items,
+            CounterWidth::default(),
+            seed,
+        )
+    }
+
+    /// Creates a new Counting Bloom filter whose counters are `counter_width` bits wide, which
+    /// hashes items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate.
+    pub fn with_counter_width_and_seed(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        counter_width: CounterWidth,
+        seed: u64,
+    ) -> Self {
+        Self::with_counter_width_and_hasher(
+            false_positive_rate,
+            estimated_items,
+            counter_width,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Creates a new Counting Bloom filter like `new`, but returns an error instead of panicking
+    /// if the parameters are invalid.
+    pub fn try_new(false_positive_rate: f64, estimated_items: usize) -> Result<Self, FlitError> {
+        Self::try_with_seed(false_positive_rate, estimated_items, rand::random())
+    }
+
+    /// Creates a new Counting Bloom filter like `with_seed`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_seed(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        seed: u64,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CountingBloomFilter<T, S> {
+    /// Creates a new Counting Bloom filter with 4-bit counters, which hashes items using the
+    /// given `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_hasher` to handle these cases without panicking.
+    pub fn with_hasher(false_positive_rate: f64, estimated_items: usize, build_hasher: S) -> Self {
+        Self::with_counter_width_and_hasher(
+            false_positive_rate,
+            estimated_items,
+            CounterWidth::default(),
+            build_hasher,
+        )
+    }
+
+    /// Creates a new Counting Bloom filter whose counters are `counter_width` bits wide, which
+    /// hashes items using the given `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_counter_width_and_hasher` to handle these cases without panicking.
+    pub fn with_counter_width_and_hasher(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        counter_width: CounterWidth,
+        build_hasher: S,
+    ) -> Self {
+        Self::try_with_counter_width_and_hasher(
+            false_positive_rate,
+            estimated_items,
+            counter_width,
+            build_hasher,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Counting Bloom filter like `with_hasher`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_hasher(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_counter_width_and_hasher(
+            false_positive_rate,
+            estimated_items,
+            CounterWidth::default(),
+            build_hasher,
+        )
+    }
+
+    /// Creates a new Counting Bloom filter like `with_counter_width_and_hasher`, but returns an
+    /// error instead of panicking if the parameters are invalid.
+    pub fn try_with_counter_width_and_hasher(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        counter_width: CounterWidth,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        let (num_counters, num_hashes) = optimal_parameters(false_positive_rate, estimated_items)?;
+
+        Ok(CountingBloomFilter {
+            n: 0,
+            m: num_counters,
+            k: num_hashes,
+            counters: PackedVec::try_new(counter_width.bits(), num_counters as usize)?,
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Adds the `item` to the filter by incrementing the appropriate counters in the filter.
+    ///
+    /// Counters that have already reached their maximum value are left unchanged.
+    pub fn add(&mut self, item: &T) {
+        let max = self.counters.max_value();
+
+        for i in indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k) {
+            let count = self.counters.get(i);
+            if count < max {
+                self.counters.set(i, count + 1);
+            }
+        }
+
+        self.n += 1;
+    }
+
+    /// Removes the `item` from the filter by decrementing the appropriate counters in the filter.
+    ///
+    /// Returns `false`, leaving the filter unchanged, if the item is definitely not in the
+    /// filter, i.e. if removing it would cause a counter to underflow. Otherwise, returns `true`.
+    ///
+    /// Note that removing an item that was never added, but for which the filter reports a
+    /// false-positive, will introduce false negatives for other items.
+    pub fn remove(&mut self, item: &T) -> bool {
+        let max = self.counters.max_value();
+        let (m, k) = (self.m, self.k);
+        let split = split_hash(item, &self.build_hasher);
+
+        // The same index may be produced more than once for a single item, in which case it was
+        // incremented more than once by `add`, and must be decremented just as many times. Each
+        // index is checked once, at its first occurrence, against the number of times it occurs.
+        for (position, i) in indices_for_hash(split, m, k).enumerate() {
+            let count = self.counters.get(i);
+            if count == max || indices_for_hash(split, m, k).take(position).any(|j| j == i) {
+                continue;
+            }
+
+            let occurrences = indices_for_hash(split, m, k).filter(|&j| j == i).count();
+            if count < occurrences as u64 {
+                return false;
+            }
+        }
+
+        for i in indices_for_hash(split, m, k) {
+            let count = self.counters.get(i);
+            if count != max {
+                self.counters.set(i, count - 1);
+            }
+        }
+
+        self.n = self.n.saturating_sub(1);
+        true
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely does not contain the item.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        for i in indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k) {
+            if self.counters.get(i) == 0 {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Calculates the current expected false positive rate given the number of items in the
+    /// filter.
+    pub fn false_positive_rate(&self) -> f64 {
+        false_positive_rate(self.n, self.m, self.k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_num_counters_and_hashes() {
+        let filter = CountingBloomFilter::<&str>::new(0.01_f64, 216553);
+
+        assert_eq!(filter.m, 2_075_674);
+        assert_eq!(filter.k, 7);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(matches!(
+            CountingBloomFilter::<&str>::try_new(1.5_f64, 10),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            CountingBloomFilter::<&str>::try_new(0.01_f64, 0),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(CountingBloomFilter::<&str>::try_with_seed(0.01_f64, 10, 42).is_ok());
+    }
+
+    #[test]
+    fn test_try_new_too_many_counters() {
+        // The number of counters is within the limit of a plain Bloom filter, but 16 bits per
+        // counter is too many.
+        assert!(matches!(
+            CountingBloomFilter::<&str>::try_with_counter_width_and_hasher(
+                0.01_f64,
+                usize::MAX >> 7,
+                CounterWidth::Sixteen,
+                XxHashBuilder::with_seed(42),
+            ),
+            Err(FlitError::CapacityOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut filter = CountingBloomFilter::new(0.03_f64, 10);
+
+        filter.add(&"Hello, world!");
+        filter.add(&"Hello, world!");
+
+        assert!(filter.false_positive_rate() > 0.0);
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(!filter.might_contain(&"Dogs are cool!"));
+
+        assert!(filter.remove(&"Hello, world!"));
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(filter.remove(&"Hello, world!"));
+        assert!(!filter.might_contain(&"Hello, world!"));
+        assert_eq!(filter.false_positive_rate(), 0_f64);
+    }
+
+    #[test]
+    fn test_remove_refuses_underflow() {
+        let mut filter = CountingBloomFilter::new(0.03_f64, 10);
+
+        filter.add(&"Hello, world!");
+
+        assert!(!filter.remove(&"Dogs are cool!"));
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert_eq!(filter.n, 1);
+    }
+
+    #[test]
+    fn test_counters_saturate() {
+        let mut filter = CountingBloomFilter::with_counter_width(0.03_f64, 10, CounterWidth::Four);
+
+        for _ in 0..20 {
+            filter.add(&"Hello, world!");
+        }
+
+        // Saturated counters are never decremented, so the item can never be fully removed.
+        for _ in 0..20 {
+            assert!(filter.remove(&"Hello, world!"));
+        }
+        assert!(filter.might_contain(&"Hello, world!"));
+    }
+}
@@ -0,0 +1,499 @@
+//! `CuckooFilter` is an alternative to a Bloom filter that stores a short fingerprint of each
+//! item in a cuckoo hash table. Like a [`CountingBloomFilter`], it supports removing items, but
+//! for false-positive rates below roughly 3% it also needs less space than a [`BloomFilter`].
+//!
+//! The table is made up of buckets, each holding a fixed number of fingerprints. Every item can
+//! be stored in one of two buckets, and the second bucket can be calculated from the first bucket
+//! and the fingerprint alone. When both buckets are full, an existing fingerprint is moved to its
+//! other bucket to make room, which may in turn move another fingerprint, and so on. If no room
+//! can be found after a fixed number of moves, the filter is considered full.
+//!
+//! # References
+//! - [Cuckoo Filter: Practically Better Than
+//!   Bloom](https://www.cs.cmu.edu/~dga/papers/cuckoo-conext2014.pdf)
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+//! [`CountingBloomFilter`]: ../counting_bloom_filter/struct.CountingBloomFilter.html
+
+use crate::error::FlitError;
+pub use crate::fingerprint::FingerprintWidth;
+use crate::hash::XxHashBuilder;
+use crate::packed_vec::PackedVec;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// The default number of fingerprints in each bucket.
+const DEFAULT_BUCKET_SIZE: usize = 4;
+
+/// The fraction of slots that can be filled before inserts start failing, for buckets of 4.
+const TARGET_LOAD_FACTOR: f64 = 0.95;
+
+/// The number of fingerprints that are moved to make room for a new item, before giving up.
+const MAX_KICKS: usize = 500;
+
+/// Represents a Cuckoo filter.
+///
+/// When constructing the filter using `new`, you need to specify the number of items you intend
+/// to store in the filter. The false-positive rate is determined by the width of the fingerprints
+/// and the size of the buckets, which can be set using `with_config`. None of these can be
+/// adjusted after creation - create a new filter instead.
+///
+/// With buckets of 4, the false-positive rate is about 3% with 8-bit fingerprints, about 0.01%
+/// with 16-bit fingerprints, and about 0.0000002% with 32-bit fingerprints.
+///
+/// # Example
+/// ```rust
+/// use flit::CuckooFilter;
+///
+/// let mut filter = CuckooFilter::new(10000);
+/// filter.insert(&"Hello, world!").unwrap();
+///
+/// assert_eq!(filter.contains(&"Hello, world!"), true); // probably true
+/// assert_eq!(filter.contains(&"Dogs are cool!"), false); // definitely false!
+///
+/// assert_eq!(filter.remove(&"Hello, world!"), true);
+/// assert_eq!(filter.contains(&"Hello, world!"), false);
+/// ```
+pub struct CuckooFilter<T, S = XxHashBuilder> {
+    n: u64,
+    num_buckets: usize,
+    bucket_size: usize,
+    fingerprints: PackedVec,
+    rng: StdRng,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Hash> CuckooFilter<T> {
+    /// Creates a new Cuckoo filter with 8-bit fingerprints and buckets of 4, that can hold at
+    /// least `capacity` items.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `capacity` is not greater than 0, or if the filter would be
+    /// too large to allocate.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_config(capacity, FingerprintWidth::default(), DEFAULT_BUCKET_SIZE)
+    }
+
+    /// Creates a new Cuckoo filter whose fingerprints are `fingerprint_width` bits wide, and
+    /// whose buckets hold `bucket_size` fingerprints each.
+    ///
+    /// Larger buckets allow the table to be filled further before inserts fail, but each lookup
+    /// has to compare more fingerprints, which raises the false-positive rate.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `capacity` or `bucket_size` is not greater than 0, or if the
+    /// filter would be too large to allocate.
+    pub fn with_config(
+        capacity: usize,
+        fingerprint_width: FingerprintWidth,
+        bucket_size: usize,
+    ) -> Self {
+        Self::with_config_and_hasher(
+            capacity,
+            fingerprint_width,
+            bucket_size,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new Cuckoo filter like `new`, but hashes items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `capacity` is not greater than 0, or if the filter would be
+    /// too large to allocate.
+    pub fn with_seed(capacity: usize, seed: u64) -> Self {
+        Self::with_hasher(capacity, XxHashBuilder::with_seed(seed))
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> CuckooFilter<T, S> {
+    /// Creates a new Cuckoo filter like `new`, but hashes items using the given `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `capacity` is not greater than 0, or if the filter would be
+    /// too large to allocate.
+    pub fn with_hasher(capacity: usize, build_hasher: S) -> Self {
+        Self::with_config_and_hasher(
+            capacity,
+            FingerprintWidth::default(),
+            DEFAULT_BUCKET_SIZE,
+            build_hasher,
+        )
+    }
+
+    /// Creates a new Cuckoo filter like `with_config`, but hashes items using the given
+    /// `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `capacity` or `bucket_size` is not greater than 0, or if the
+    /// filter would be too large to allocate. Use `try_with_config_and_hasher` to handle these
+    /// cases without panicking.
+    pub fn with_config_and_hasher(
+        capacity: usize,
+        fingerprint_width: FingerprintWidth,
+        bucket_size: usize,
+        build_hasher: S,
+    ) -> Self {
+        Self::try_with_config_and_hasher(capacity, fingerprint_width, bucket_size, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Cuckoo filter like `with_config_and_hasher`, but returns an error instead
+    /// of panicking if the parameters are invalid.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::hash::XxHashBuilder;
+    /// use flit::{CuckooFilter, FingerprintWidth, FlitError};
+    ///
+    /// let filter = CuckooFilter::<&str>::try_with_config_and_hasher(
+    ///     10000,
+    ///     FingerprintWidth::Sixteen,
+    ///     0,
+    ///     XxHashBuilder::with_seed(42),
+    /// );
+    ///
+    /// assert!(matches!(filter, Err(FlitError::InvalidParameter("bucket_size"))));
+    /// ```
+    pub fn try_with_config_and_hasher(
+        capacity: usize,
+        fingerprint_width: FingerprintWidth,
+        bucket_size: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        if capacity == 0 {
+            return Err(FlitError::ZeroCapacity);
+        }
+        if bucket_size == 0 {
+            return Err(FlitError::InvalidParameter("bucket_size"));
+        }
+
+        // The number of buckets must be a power of two, so that the alternate bucket of a
+        // fingerprint can be found by XOR-ing the bucket index.
+        let num_buckets = ((capacity as f64 / TARGET_LOAD_FACTOR).ceil() as usize)
+            .div_ceil(bucket_size)
+            .checked_next_power_of_two()
+            .ok_or(FlitError::CapacityOverflow)?;
+        let num_slots = num_buckets
+            .checked_mul(bucket_size)
+            .ok_or(FlitError::CapacityOverflow)?;
+        let fingerprints = PackedVec::try_new(fingerprint_width.bits(), num_slots)?;
+
+        // The fingerprints to evict are picked using the hasher, so that a filter created with a
+        // fixed seed behaves the same every time.
+        let rng = StdRng::seed_from_u64(build_hasher.hash_one(num_slots as u64));
+
+        Ok(CuckooFilter {
+            n: 0,
+            num_buckets,
+            bucket_size,
+            fingerprints,
+            rng,
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Inserts the `item` into the filter.
+    ///
+    /// The same item can be inserted more than once, in which case it has to be removed just as
+    /// many times before the filter stops reporting it.
+    ///
+    /// Returns `FlitError::FilterFull`, leaving the filter unchanged, if no room could be made
+    /// for the item.
+    pub fn insert(&mut self, item: &T) -> Result<(), FlitError> {
+        let (fingerprint, first) = self.fingerprint_and_bucket(item);
+        let second = self.alternate_bucket(first, fingerprint);
+
+        if self.insert_into_bucket(first, fingerprint)
+            || self.insert_into_bucket(second, fingerprint)
+        {
+            self.n += 1;
+            return Ok(());
+        }
+
+        // Move fingerprints to their alternate buckets until one of them finds an empty slot,
+        // remembering which slots were swapped so that they can be restored on failure.
+        let mut bucket = if self.rng.gen() { first } else { second };
+        let mut fingerprint = fingerprint;
+        let mut swapped = Vec::with_capacity(MAX_KICKS);
+
+        for _ in 0..MAX_KICKS {
+            let slot = bucket * self.bucket_size + self.rng.gen_range(0, self.bucket_size);
+            fingerprint = self.swap(slot, fingerprint);
+            swapped.push(slot);
+
+            bucket = self.alternate_bucket(bucket, fingerprint);
+            if self.insert_into_bucket(bucket, fingerprint) {
+                self.n += 1;
+                return Ok(());
+            }
+        }
+
+        for slot in swapped.into_iter().rev() {
+            fingerprint = self.swap(slot, fingerprint);
+        }
+
+        Err(FlitError::FilterFull)
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely does not contain the item.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn contains(&self, item: &T) -> bool {
+        let (fingerprint, first) = self.fingerprint_and_bucket(item);
+        let second = self.alternate_bucket(first, fingerprint);
+
+        self.find_in_bucket(first, fingerprint).is_some()
+            || self.find_in_bucket(second, fingerprint).is_some()
+    }
+
+    /// Removes the `item` from the filter.
+    ///
+    /// Returns `false`, leaving the filter unchanged, if the item is definitely not in the
+    /// filter. Otherwise, returns `true`.
+    ///
+    /// Note that removing an item that was never added, but for which the filter reports a
+    /// false-positive, will introduce false negatives for other items.
+    pub fn remove(&mut self, item: &T) -> bool {
+        let (fingerprint, first) = self.fingerprint_and_bucket(item);
+        let second = self.alternate_bucket(first, fingerprint);
+
+        let slot = self
+            .find_in_bucket(first, fingerprint)
+            .or_else(|| self.find_in_bucket(second, fingerprint));
+
+        match slot {
+            Some(slot) => {
+                self.fingerprints.set(slot, 0);
+                self.n -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the fingerprint of `item`, which is never zero, and the index of its first bucket.
+    fn fingerprint_and_bucket(&self, item: &T) -> (u64, usize) {
+        let hash = self.build_hasher.hash_one(item);
+
+        // Zero marks an empty slot, so it cannot be used as a fingerprint.
+        let fingerprint = ((hash >> 32) % self.fingerprints.max_value()) + 1;
+        let bucket = hash as usize & (self.num_buckets - 1);
+
+        (fingerprint, bucket)
+    }
+
+    /// Returns the other bucket that a fingerprint stored in `bucket` may be stored in.
+    ///
+    /// This is its own inverse, so the first bucket can also be found from the second one.
+    fn alternate_bucket(&self, bucket: usize, fingerprint: u64) -> usize {
+        let hash = fingerprint.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 32;
+
+        (bucket ^ hash as usize) & (self.num_buckets - 1)
+    }
+
+    /// Stores `fingerprint` in an empty slot of `bucket`, or returns `false` if it is full.
+    fn insert_into_bucket(&mut self, bucket: usize, fingerprint: u64) -> bool {
+        match self.find_in_bucket(bucket, 0) {
+            Some(slot) => {
+                self.fingerprints.set(slot, fingerprint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the index of the first slot of `bucket` holding `fingerprint`.
+    fn find_in_bucket(&self, bucket: usize, fingerprint: u64) -> Option<usize> {
+        let start = bucket * self.bucket_size;
+
+        (start..start + self.bucket_size).find(|&slot| self.fingerprints.get(slot) == fingerprint)
+    }
+
+    /// Stores `fingerprint` in `slot`, and returns the fingerprint that was there before.
+    fn swap(&mut self, slot: usize, fingerprint: u64) -> u64 {
+        let previous = self.fingerprints.get(slot);
+        self.fingerprints.set(slot, fingerprint);
+        previous
+    }
+}
+
+impl<T, S> CuckooFilter<T, S> {
+    /// Returns the number of items in the filter.
+    pub fn len(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns `true` if the filter holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Returns the number of fingerprints that the filter has room for.
+    ///
+    /// Inserts usually start failing somewhat before the filter is completely full.
+    pub fn num_slots(&self) -> u64 {
+        (self.num_buckets * self.bucket_size) as u64
+    }
+
+    /// Returns the number of fingerprints in each bucket.
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// Returns the fraction of slots in the filter that hold a fingerprint.
+    pub fn load_factor(&self) -> f64 {
+        self.n as f64 / self.num_slots() as f64
+    }
+
+    /// Calculates the current expected false positive rate given the number of items in the
+    /// filter.
+    ///
+    /// A lookup compares the item's fingerprint against the occupied slots of two buckets, each
+    /// of which matches with a probability of `1 / (2^f - 1)`.
+    pub fn false_positive_rate(&self) -> f64 {
+        let occupied = 2_f64 * self.bucket_size as f64 * self.load_factor();
+        let no_match = 1_f64 - 1_f64 / self.fingerprints.max_value() as f64;
+
+        1_f64 - no_match.powf(occupied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_num_slots() {
+        let filter = CuckooFilter::<u32>::new(1000);
+
+        // 1000 / 0.95 = 1053 slots, rounded up to 512 buckets of 4.
+        assert_eq!(filter.num_slots(), 2048);
+        assert_eq!(filter.bucket_size(), 4);
+
+        let filter = CuckooFilter::<u32>::with_config(1000, FingerprintWidth::Sixteen, 2);
+        assert_eq!(filter.num_slots(), 1024 * 2);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(matches!(
+            CuckooFilter::<u32>::try_with_config_and_hasher(
+                0,
+                FingerprintWidth::Eight,
+                4,
+                XxHashBuilder::with_seed(42)
+            ),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(matches!(
+            CuckooFilter::<u32>::try_with_config_and_hasher(
+                usize::MAX,
+                FingerprintWidth::Eight,
+                4,
+                XxHashBuilder::with_seed(42)
+            ),
+            Err(FlitError::CapacityOverflow)
+        ));
+        // The number of buckets fits, but not the 32-bit fingerprints in them.
+        assert!(matches!(
+            CuckooFilter::<u32>::try_with_config_and_hasher(
+                usize::MAX >> 6,
+                FingerprintWidth::ThirtyTwo,
+                4,
+                XxHashBuilder::with_seed(42)
+            ),
+            Err(FlitError::CapacityOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut filter = CuckooFilter::with_seed(10, 42);
+
+        filter.insert(&"Hello, world!").unwrap();
+        filter.insert(&"Hello, world!").unwrap();
+
+        assert_eq!(filter.len(), 2);
+        assert!(filter.false_positive_rate() > 0.0);
+        assert!(filter.contains(&"Hello, world!"));
+        assert!(!filter.contains(&"Dogs are cool!"));
+
+        assert!(!filter.remove(&"Dogs are cool!"));
+        assert!(filter.remove(&"Hello, world!"));
+        assert!(filter.contains(&"Hello, world!"));
+        assert!(filter.remove(&"Hello, world!"));
+        assert!(!filter.contains(&"Hello, world!"));
+        assert!(filter.is_empty());
+        assert_eq!(filter.false_positive_rate(), 0_f64);
+    }
+
+    #[test]
+    fn test_insert_fails_when_full() {
+        let mut filter = CuckooFilter::with_seed(100, 42);
+
+        let mut inserted = Vec::new();
+        for i in 0.. {
+            match filter.insert(&i) {
+                Ok(()) => inserted.push(i),
+                Err(err) => {
+                    assert!(matches!(err, FlitError::FilterFull));
+                    break;
+                }
+            }
+        }
+
+        // A failed insert must not evict any of the items that were already in the filter.
+        assert!(filter.load_factor() > 0.9);
+        assert_eq!(filter.len(), inserted.len() as u64);
+        assert!(inserted.iter().all(|i| filter.contains(i)));
+    }
+
+    #[test]
+    fn test_seeded_filters_evict_the_same_fingerprints() {
+        let mut a = CuckooFilter::with_seed(100, 42);
+        let mut b = CuckooFilter::with_seed(100, 42);
+
+        for i in 0..1000 {
+            assert_eq!(a.insert(&i).is_ok(), b.insert(&i).is_ok());
+        }
+
+        assert_eq!(a.fingerprints, b.fingerprints);
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = CuckooFilter::with_config(100_000, FingerprintWidth::Sixteen, 4);
+
+        for i in 0..100_000 {
+            filter.insert(&i).unwrap();
+        }
+
+        let false_positives = (100_000..200_000).filter(|i| filter.contains(i)).count();
+
+        assert!((0..100_000).all(|i| filter.contains(&i)));
+        assert!(filter.false_positive_rate() < 0.0002);
+        assert!(false_positives < 30);
+    }
+}
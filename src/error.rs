@@ -0,0 +1,126 @@
+//! Error types returned by the fallible operations in this crate.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// The error type for fallible operations in this crate.
+#[derive(Debug)]
+pub enum FlitError {
+    /// The requested false positive rate was not between 0 and 1 (non-inclusive).
+    InvalidFalsePositiveRate(f64),
+    /// The requested number of items was zero.
+    ZeroCapacity,
+    /// The filter would need more bits than can be allocated on this platform.
+    CapacityOverflow,
+    /// A parameter needed to size the filter was not provided.
+    MissingParameter(&'static str),
+    /// Two parameters were provided that contradict each other.
+    ConflictingParameters(&'static str, &'static str),
+    /// A parameter was provided with a value that cannot be used, such as zero bits.
+    InvalidParameter(&'static str),
+    /// Two filters could not be combined, because they have a different number of bits, number
+    /// of hashes or hasher.
+    IncompatibleFilters,
+    /// There was no room left in the filter for another item.
+    FilterFull,
+    /// A filter could not be constructed from the given items.
+    ConstructionFailed,
+    /// A serialized filter could not be read.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FlitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlitError::InvalidFalsePositiveRate(rate) => write!(
+                f,
+                "false positive rate must be between 0 and 1 (non-inclusive), got {}",
+                rate
+            ),
+            FlitError::ZeroCapacity => {
+                write!(f, "number of estimated items must be greater than zero")
+            }
+            FlitError::CapacityOverflow => write!(f, "filter is too large to be allocated"),
+            FlitError::MissingParameter(name) => write!(f, "missing parameter `{}`", name),
+            FlitError::ConflictingParameters(first, second) => write!(
+                f,
+                "parameters `{}` and `{}` conflict with each other",
+                first, second
+            ),
+            FlitError::InvalidParameter(name) => write!(f, "invalid value for `{}`", name),
+            FlitError::IncompatibleFilters => write!(f, "filters are not compatible"),
+            FlitError::FilterFull => write!(f, "filter is full"),
+            FlitError::ConstructionFailed => {
+                write!(f, "filter could not be constructed from the given items")
+            }
+            FlitError::Decode(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for FlitError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FlitError::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<DecodeError> for FlitError {
+    fn from(err: DecodeError) -> Self {
+        FlitError::Decode(err)
+    }
+}
+
+/// An error returned when a serialized filter cannot be read.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The underlying reader returned an error, or ran out of data before the whole filter was
+    /// read.
+    Io(io::Error),
+    /// The data does not start with the magic number of a serialized filter.
+    InvalidMagic,
+    /// The data was written using a format version that this version of the crate cannot read.
+    UnsupportedVersion(u8),
+    /// The filter was written using a hasher that this version of the crate does not know about.
+    UnsupportedHasher(u8),
+    /// The parameters or contents of the filter are inconsistent with each other.
+    InvalidData,
+    /// The checksum stored with the filter does not match its contents.
+    ChecksumMismatch,
+    /// There was more data after the end of the filter.
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "failed to read filter: {}", err),
+            DecodeError::InvalidMagic => write!(f, "data is not a serialized filter"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {}", version)
+            }
+            DecodeError::UnsupportedHasher(id) => write!(f, "unsupported hasher {}", id),
+            DecodeError::InvalidData => write!(f, "filter parameters are invalid"),
+            DecodeError::ChecksumMismatch => write!(f, "filter checksum does not match"),
+            DecodeError::TrailingBytes => write!(f, "unexpected data after the end of the filter"),
+        }
+    }
+}
+
+impl Error for DecodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DecodeError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        DecodeError::Io(err)
+    }
+}
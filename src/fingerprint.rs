@@ -0,0 +1,31 @@
+//! Settings shared by the filters that store fingerprints of their items, rather than setting bits
+//! for them.
+
+/// The number of bits used for each fingerprint in a [`CuckooFilter`] or a
+/// [`BinaryFuseFilter`].
+///
+/// Longer fingerprints give a lower false-positive rate, but take up proportionally more space.
+/// See the documentation of each filter for the false-positive rate of each width.
+///
+/// [`BinaryFuseFilter`]: ../binary_fuse_filter/struct.BinaryFuseFilter.html
+/// [`CuckooFilter`]: ../cuckoo_filter/struct.CuckooFilter.html
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FingerprintWidth {
+    /// 8-bit fingerprints.
+    #[default]
+    Eight,
+    /// 16-bit fingerprints.
+    Sixteen,
+    /// 32-bit fingerprints.
+    ThirtyTwo,
+}
+
+impl FingerprintWidth {
+    pub(crate) fn bits(self) -> u32 {
+        match self {
+            FingerprintWidth::Eight => 8,
+            FingerprintWidth::Sixteen => 16,
+            FingerprintWidth::ThirtyTwo => 32,
+        }
+    }
+}
@@ -0,0 +1,62 @@
+//! Hashing support shared by the filters in this crate.
+//!
+//! All filters hash their items with [xxHash](https://github.com/Cyan4973/xxHash), using the
+//! 64-bit variant provided by [twox-hash](https://crates.io/crates/twox-hash). The hasher is
+//! seeded with a random value by default, but an explicit seed can be provided so that filters
+//! built by one process can be queried by another.
+
+use rand::{self, Rng};
+use std::hash::BuildHasher;
+use twox_hash::XxHash64;
+
+/// Creates `XxHash64` hashers with a fixed seed.
+///
+/// Two builders with the same seed always produce the same hash for the same item, even across
+/// processes and machines.
+///
+/// # Example
+/// ```rust
+/// use flit::hash::XxHashBuilder;
+/// use std::hash::BuildHasher;
+///
+/// let a = XxHashBuilder::with_seed(42);
+/// let b = XxHashBuilder::with_seed(42);
+///
+/// assert_eq!(a.hash_one("Hello, world!"), b.hash_one("Hello, world!"));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct XxHashBuilder {
+    seed: u64,
+}
+
+impl XxHashBuilder {
+    /// Creates a new builder with the given `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        XxHashBuilder { seed }
+    }
+
+    /// Creates a new builder with a randomly chosen seed.
+    pub fn random() -> Self {
+        Self::with_seed(rand::thread_rng().gen())
+    }
+
+    /// Returns the seed used by the hashers created by this builder.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl Default for XxHashBuilder {
+    /// Creates a new builder with a randomly chosen seed.
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+impl BuildHasher for XxHashBuilder {
+    type Hasher = XxHash64;
+
+    fn build_hasher(&self) -> XxHash64 {
+        XxHash64::with_seed(self.seed)
+    }
+}
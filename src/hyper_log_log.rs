@@ -0,0 +1,686 @@
+//! `HyperLogLog` estimates the number of distinct items that have been added to it, using a small,
+//! fixed amount of memory.
+//!
+//! Each item is hashed to a 64-bit value. The first `p` bits of the hash choose one of `2^p`
+//! registers, and the register records the largest number of leading zeros (plus one) seen in
+//! the rest of the hash. The more distinct items there are, the longer the longest run of zeros
+//! is likely to be, so the number of distinct items can be estimated from the registers. The
+//! relative standard error of the estimate is about `1.04 / sqrt(2^p)`.
+//!
+//! Like HyperLogLog++, the sketch starts out with a sparse representation, which only stores the
+//! registers that have been set, with a higher precision. This makes small sketches much smaller
+//! and their estimates much more accurate. New entries are collected in a small unsorted buffer,
+//! and merged into the sorted list of entries in batches. Once the sparse representation would be
+//! larger than the dense one, the sketch switches to the dense representation.
+//!
+//! Rather than the empirical bias correction tables of HyperLogLog++, dense sketches are
+//! estimated using Ertl's improved estimator, which corrects the bias of the original estimator
+//! for both small and large cardinalities without any tables.
+//!
+//! # References
+//! - [HyperLogLog: the analysis of a near-optimal cardinality estimation
+//!   algorithm](http://algo.inria.fr/flajolet/Publications/FlFuGaMe07.pdf)
+//! - [HyperLogLog in Practice: Algorithmic Engineering of a State of The Art Cardinality
+//!   Estimation Algorithm](https://research.google/pubs/pub40671/)
+//! - [New cardinality estimation algorithms for HyperLogLog
+//!   sketches](https://arxiv.org/abs/1702.01284)
+
+use crate::bloom_filter::split_hash;
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use crate::packed_vec::PackedVec;
+use std::f64::consts::LN_2;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// The smallest supported precision.
+const MIN_PRECISION: u32 = 4;
+
+/// The largest supported precision.
+const MAX_PRECISION: u32 = 18;
+
+/// The precision of the index of the sparse representation.
+const SPARSE_PRECISION: u32 = 25;
+
+/// The number of bits in each register of the dense representation, which is enough to hold
+/// ranks of up to `64 - MIN_PRECISION + 1`.
+const REGISTER_BITS: u32 = 6;
+
+/// Represents a HyperLogLog cardinality estimator.
+///
+/// When constructing the estimator using `new`, you need to specify its precision `p`, between 4
+/// and 18. The dense representation of the estimator uses `2^p` 6-bit registers, and its relative
+/// standard error is about `1.04 / sqrt(2^p)`: a precision of 14 uses 12 KiB, and estimates
+/// cardinalities to within about 0.8%.
+///
+/// # Example
+/// ```rust
+/// use flit::HyperLogLog;
+///
+/// let mut hll = HyperLogLog::new(14);
+///
+/// for i in 0..100_000 {
+///     hll.add(&(i % 1000));
+/// }
+///
+/// assert!((990..=1010).contains(&hll.count()));
+/// ```
+pub struct HyperLogLog<T, S = XxHashBuilder> {
+    precision: u32,
+    registers: Registers,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+#[derive(Clone)]
+enum Registers {
+    /// Entries, each holding a `SPARSE_PRECISION`-bit register index followed by a
+    /// `REGISTER_BITS`-bit rank. `entries` is sorted, with at most one entry for each index, and
+    /// `buffer` holds the entries added since it was last merged into `entries`.
+    Sparse {
+        entries: Vec<u32>,
+        buffer: Vec<u32>,
+    },
+    Dense(PackedVec),
+}
+
+impl<T: Hash> HyperLogLog<T> {
+    /// Creates a new HyperLogLog estimator with the given `precision`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `precision` is not between 4 and 18.
+    pub fn new(precision: u32) -> Self {
+        Self::with_hasher(precision, XxHashBuilder::random())
+    }
+
+    /// Creates a new HyperLogLog estimator like `new`, but hashes items using the given `seed`.
+    ///
+    /// Estimators created with the same precision and seed can be merged.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `precision` is not between 4 and 18.
+    pub fn with_seed(precision: u32, seed: u64) -> Self {
+        Self::with_hasher(precision, XxHashBuilder::with_seed(seed))
+    }
+
+    /// Creates a new HyperLogLog estimator like `new`, but returns an error instead of panicking
+    /// if the precision is invalid.
+    pub fn try_new(precision: u32) -> Result<Self, FlitError> {
+        Self::try_with_hasher(precision, XxHashBuilder::random())
+    }
+}
+
+impl<T> HyperLogLog<T> {
+    /// Returns the seed used to hash items added to the estimator.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> HyperLogLog<T, S> {
+    /// Creates a new HyperLogLog estimator like `new`, but hashes items using the given
+    /// `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `precision` is not between 4 and 18. Use `try_with_hasher` to
+    /// handle this case without panicking.
+    pub fn with_hasher(precision: u32, build_hasher: S) -> Self {
+        Self::try_with_hasher(precision, build_hasher).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new HyperLogLog estimator like `with_hasher`, but returns an error instead of
+    /// panicking if the precision is invalid.
+    pub fn try_with_hasher(precision: u32, build_hasher: S) -> Result<Self, FlitError> {
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+            return Err(FlitError::InvalidParameter("precision"));
+        }
+
+        Ok(HyperLogLog {
+            precision,
+            registers: Registers::Sparse {
+                entries: Vec::new(),
+                buffer: Vec::new(),
+            },
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns a reference to the estimator's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Adds the `item` to the estimator.
+    pub fn add(&mut self, item: &T) {
+        self.add_hash(split_hash(item, &self.build_hasher).0);
+    }
+}
+
+impl<T, S> HyperLogLog<T, S> {
+    /// Adds an item to the estimator, given its 64-bit hash.
+    ///
+    /// The hash of an item is `hasher().hash_one(item)`. This is the same hash that a
+    /// [`BloomFilter`] with the same hasher derives the item's indices from.
+    ///
+    /// [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+    pub fn add_hash(&mut self, hash: u64) {
+        let max_sparse_len = self.max_sparse_len();
+
+        match &mut self.registers {
+            Registers::Sparse { entries, buffer } => {
+                // Inserting every entry into the sorted list would take quadratic time, so they
+                // are merged in batches of up to a quarter of the largest sparse list instead.
+                buffer.push(sparse_entry(hash));
+                if buffer.len() < (max_sparse_len / 4).max(1) {
+                    return;
+                }
+
+                *entries = merged_entries(entries, buffer);
+                buffer.clear();
+
+                if entries.len() > max_sparse_len {
+                    self.convert_to_dense();
+                }
+            }
+            Registers::Dense(registers) => {
+                let (index, rank) = dense_register(hash, self.precision);
+                if rank > registers.get(index) {
+                    registers.set(index, rank);
+                }
+            }
+        }
+    }
+
+    /// Estimates the number of distinct items that have been added to the estimator.
+    pub fn count(&self) -> u64 {
+        match &self.registers {
+            Registers::Sparse { entries, buffer } => {
+                // Linear counting over the registers of the sparse representation, which is very
+                // accurate while only a small fraction of them are set.
+                let m = (1_u64 << SPARSE_PRECISION) as f64;
+                let zeros = m - merged_entries(entries, buffer).len() as f64;
+
+                (m * (m / zeros).ln()).round() as u64
+            }
+            Registers::Dense(registers) => estimate_dense(registers, self.precision).round() as u64,
+        }
+    }
+
+    /// Returns `true` if no items have been added to the estimator.
+    pub fn is_empty(&self) -> bool {
+        match &self.registers {
+            Registers::Sparse { entries, buffer } => entries.is_empty() && buffer.is_empty(),
+            Registers::Dense(registers) => (0..registers.len()).all(|i| registers.get(i) == 0),
+        }
+    }
+
+    /// Returns the precision of the estimator.
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// Returns `true` if the estimator still uses the sparse representation.
+    pub fn is_sparse(&self) -> bool {
+        match self.registers {
+            Registers::Sparse { .. } => true,
+            Registers::Dense(_) => false,
+        }
+    }
+
+    /// Returns the relative standard error of estimates once the estimator uses the dense
+    /// representation.
+    pub fn standard_error(&self) -> f64 {
+        1.04_f64 / f64::from(1_u32 << self.precision).sqrt()
+    }
+
+    /// Returns the number of entries after which the sparse representation, using 32 bits per
+    /// entry, would be larger than the dense representation.
+    fn max_sparse_len(&self) -> usize {
+        (1_usize << self.precision) * REGISTER_BITS as usize / 32
+    }
+
+    /// Switches to the dense representation.
+    fn convert_to_dense(&mut self) {
+        if let Registers::Sparse { entries, buffer } = &self.registers {
+            let mut registers = PackedVec::new(REGISTER_BITS, 1 << self.precision);
+            for &entry in entries.iter().chain(buffer) {
+                let (index, rank) = dense_register_from_sparse(entry, self.precision);
+                if rank > registers.get(index) {
+                    registers.set(index, rank);
+                }
+            }
+
+            self.registers = Registers::Dense(registers);
+        }
+    }
+}
+
+impl<T, S: PartialEq> HyperLogLog<T, S> {
+    /// Adds all of the items in `other` to this estimator.
+    ///
+    /// Returns an error, leaving this estimator unchanged, if the estimators do not have the same
+    /// precision and hasher.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::HyperLogLog;
+    ///
+    /// let mut a = HyperLogLog::with_seed(14, 42);
+    /// let mut b = HyperLogLog::with_seed(14, 42);
+    /// a.add(&"Hello, world!");
+    /// b.add(&"Hello, world!");
+    /// b.add(&"Dogs are cool!");
+    ///
+    /// a.merge_with(&b).unwrap();
+    ///
+    /// assert_eq!(a.count(), 2);
+    /// ```
+    pub fn merge_with(&mut self, other: &Self) -> Result<(), FlitError> {
+        if self.precision != other.precision || self.build_hasher != other.build_hasher {
+            return Err(FlitError::IncompatibleFilters);
+        }
+
+        let max_sparse_len = self.max_sparse_len();
+
+        match (&mut self.registers, &other.registers) {
+            (
+                Registers::Sparse { entries, buffer },
+                Registers::Sparse {
+                    entries: other_entries,
+                    buffer: other_buffer,
+                },
+            ) => {
+                buffer.extend_from_slice(other_buffer);
+                *entries = merge_sparse(&merged_entries(entries, buffer), other_entries);
+                buffer.clear();
+
+                if entries.len() > max_sparse_len {
+                    self.convert_to_dense();
+                }
+            }
+            (_, other_registers) => {
+                self.convert_to_dense();
+
+                if let Registers::Dense(registers) = &mut self.registers {
+                    let mut merge_register = |index: usize, rank: u64| {
+                        if rank > registers.get(index) {
+                            registers.set(index, rank);
+                        }
+                    };
+
+                    match other_registers {
+                        Registers::Sparse {
+                            entries: other_entries,
+                            buffer: other_buffer,
+                        } => {
+                            for &entry in other_entries.iter().chain(other_buffer) {
+                                let (index, rank) =
+                                    dense_register_from_sparse(entry, self.precision);
+                                merge_register(index, rank);
+                            }
+                        }
+                        Registers::Dense(other_registers) => {
+                            for index in 0..other_registers.len() {
+                                merge_register(index, other_registers.get(index));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T, S: Clone> Clone for HyperLogLog<T, S> {
+    fn clone(&self) -> Self {
+        HyperLogLog {
+            precision: self.precision,
+            registers: self.registers.clone(),
+            build_hasher: self.build_hasher.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Returns the sparse entry for a hash: its `SPARSE_PRECISION`-bit register index, followed by
+/// the number of leading zeros in the rest of the hash, plus one.
+fn sparse_entry(hash: u64) -> u32 {
+    let index = (hash >> (64 - SPARSE_PRECISION)) as u32;
+    let rank = (hash << SPARSE_PRECISION)
+        .leading_zeros()
+        .min(64 - SPARSE_PRECISION)
+        + 1;
+
+    (index << REGISTER_BITS) | rank
+}
+
+/// Returns the index and rank of the dense register for a hash.
+fn dense_register(hash: u64, precision: u32) -> (usize, u64) {
+    let index = (hash >> (64 - precision)) as usize;
+    let rank = (hash << precision).leading_zeros().min(64 - precision) + 1;
+
+    (index, u64::from(rank))
+}
+
+/// Returns the index and rank of the dense register for a sparse entry, which are the same as
+/// those that `dense_register` returns for the original hash.
+fn dense_register_from_sparse(entry: u32, precision: u32) -> (usize, u64) {
+    let extra_bits = SPARSE_PRECISION - precision;
+    let sparse_index = entry >> REGISTER_BITS;
+    let index = (sparse_index >> extra_bits) as usize;
+
+    // The bits of the sparse index that are not part of the dense index are the first bits that
+    // the dense rank is computed from.
+    let extra = sparse_index & ((1 << extra_bits) - 1);
+    let rank = if extra == 0 {
+        extra_bits + (entry & ((1 << REGISTER_BITS) - 1))
+    } else {
+        extra_bits - (32 - extra.leading_zeros()) + 1
+    };
+
+    (index, u64::from(rank))
+}
+
+/// Returns the sorted list of sparse `entries`, with the unsorted entries in `buffer` merged in.
+fn merged_entries(entries: &[u32], buffer: &[u32]) -> Vec<u32> {
+    // Sorting the entries themselves orders them by index, and then by rank, so the last entry
+    // for each index has the largest rank.
+    let mut sorted = buffer.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup_by(|entry, previous| {
+        let same_index = *entry >> REGISTER_BITS == *previous >> REGISTER_BITS;
+        if same_index {
+            *previous = *entry;
+        }
+        same_index
+    });
+
+    merge_sparse(entries, &sorted)
+}
+
+/// Merges two sorted lists of sparse entries, keeping the largest rank for each index.
+fn merge_sparse(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+
+    while i < a.len() && j < b.len() {
+        let (a_index, b_index) = (a[i] >> REGISTER_BITS, b[j] >> REGISTER_BITS);
+        if a_index < b_index {
+            merged.push(a[i]);
+            i += 1;
+        } else if b_index < a_index {
+            merged.push(b[j]);
+            j += 1;
+        } else {
+            merged.push(a[i].max(b[j]));
+            i += 1;
+            j += 1;
+        }
+    }
+    merged.extend_from_slice(&a[i..]);
+    merged.extend_from_slice(&b[j..]);
+
+    merged
+}
+
+/// Estimates the cardinality of a dense set of registers, using Ertl's improved raw estimator.
+fn estimate_dense(registers: &PackedVec, precision: u32) -> f64 {
+    let q = 64 - precision as usize;
+    let m = registers.len() as f64;
+
+    let mut histogram = vec![0_u64; q + 2];
+    for i in 0..registers.len() {
+        histogram[registers.get(i) as usize] += 1;
+    }
+    if histogram[0] == registers.len() as u64 {
+        return 0_f64;
+    }
+
+    let mut z = m * tau(1_f64 - histogram[q + 1] as f64 / m);
+    for &count in histogram[1..=q].iter().rev() {
+        z = 0.5 * (z + count as f64);
+    }
+    z += m * sigma(histogram[0] as f64 / m);
+
+    m * m / (2_f64 * LN_2 * z)
+}
+
+/// The `sigma` function of Ertl's estimator, which corrects for registers that are still zero.
+fn sigma(mut x: f64) -> f64 {
+    let mut y = 1_f64;
+    let mut z = x;
+
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+
+        if z == previous {
+            return z;
+        }
+    }
+}
+
+/// The `tau` function of Ertl's estimator, which corrects for registers that have reached their
+/// maximum value.
+fn tau(mut x: f64) -> f64 {
+    if x == 0_f64 || x == 1_f64 {
+        return 0_f64;
+    }
+
+    let mut y = 1_f64;
+    let mut z = 1_f64 - x;
+
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1_f64 - x).powi(2) * y;
+
+        if z == previous {
+            return z / 3_f64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relative_error(hll: &HyperLogLog<u64>, actual: u64) -> f64 {
+        (hll.count() as f64 - actual as f64).abs() / actual as f64
+    }
+
+    #[test]
+    fn test_empty() {
+        let hll = HyperLogLog::<u64>::new(14);
+
+        assert!(hll.is_empty());
+        assert!(hll.is_sparse());
+        assert_eq!(hll.count(), 0);
+    }
+
+    #[test]
+    fn test_invalid_precision() {
+        assert!(matches!(
+            HyperLogLog::<u64>::try_new(3),
+            Err(FlitError::InvalidParameter("precision"))
+        ));
+        assert!(matches!(
+            HyperLogLog::<u64>::try_new(19),
+            Err(FlitError::InvalidParameter("precision"))
+        ));
+    }
+
+    #[test]
+    fn test_sparse_is_exact_for_small_counts() {
+        let mut hll = HyperLogLog::with_seed(14, 42);
+
+        for i in 0..100 {
+            hll.add(&i);
+            hll.add(&i);
+        }
+
+        assert!(hll.is_sparse());
+        assert_eq!(hll.count(), 100);
+    }
+
+    #[test]
+    fn test_accuracy() {
+        let mut hll = HyperLogLog::with_seed(12, 42);
+        let mut actual = 0;
+
+        for &target in &[1000, 10_000, 100_000, 1_000_000] {
+            while actual < target {
+                hll.add(&actual);
+                actual += 1;
+            }
+
+            // Three standard errors, with a precision of 12.
+            assert!(relative_error(&hll, actual) < 0.05);
+        }
+        assert!(!hll.is_sparse());
+    }
+
+    #[test]
+    fn test_switching_to_dense_keeps_registers() {
+        let mut sparse = HyperLogLog::with_seed(10, 42);
+        let mut dense = HyperLogLog::with_seed(10, 42);
+        dense.convert_to_dense();
+
+        for i in 0..100_u64 {
+            sparse.add(&i);
+            dense.add(&i);
+        }
+        sparse.convert_to_dense();
+
+        match (&sparse.registers, &dense.registers) {
+            (Registers::Dense(a), Registers::Dense(b)) => assert_eq!(a, b),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_sparse_buffer() {
+        let mut hll = HyperLogLog::<u64>::with_seed(14, 42);
+        let mut hashes: Vec<u64> = (0..1000_u64).map(|i| hll.hasher().hash_one(i)).collect();
+
+        // Every entry is added several times, with different ranks for the same index, and across
+        // several batches.
+        for &hash in &hashes {
+            hll.add_hash(hash | 0xff);
+        }
+        hashes.reverse();
+        for &hash in &hashes {
+            hll.add_hash(hash);
+            hll.add_hash(hash | 0xff);
+        }
+
+        assert!(hll.is_sparse());
+        assert_eq!(hll.count(), 1000);
+
+        let mut expected = HyperLogLog::<u64>::with_seed(14, 42);
+        expected.convert_to_dense();
+        for &hash in &hashes {
+            expected.add_hash(hash);
+        }
+        hll.convert_to_dense();
+        match (&hll.registers, &expected.registers) {
+            (Registers::Dense(a), Registers::Dense(b)) => assert_eq!(a, b),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = HyperLogLog::with_seed(12, 42);
+        let mut b = HyperLogLog::with_seed(12, 42);
+        let mut both = HyperLogLog::with_seed(12, 42);
+
+        for i in 0..100_000_u64 {
+            a.add(&i);
+            both.add(&i);
+        }
+        for i in 50_000..50_500_u64 {
+            b.add(&i);
+            both.add(&i);
+        }
+        for i in 1_000_000..1_000_100_u64 {
+            b.add(&i);
+            both.add(&i);
+        }
+
+        assert!(!a.is_sparse());
+        assert!(b.is_sparse());
+
+        let mut sparse_into_dense = a.clone();
+        sparse_into_dense.merge_with(&b).unwrap();
+        assert_eq!(sparse_into_dense.count(), both.count());
+
+        let mut dense_into_sparse = b.clone();
+        dense_into_sparse.merge_with(&a).unwrap();
+        assert_eq!(dense_into_sparse.count(), both.count());
+    }
+
+    #[test]
+    fn test_merge_sparse() {
+        let mut a = HyperLogLog::with_seed(14, 42);
+        let mut b = HyperLogLog::with_seed(14, 42);
+
+        for i in 0..100_u64 {
+            a.add(&i);
+        }
+        for i in 50..150_u64 {
+            b.add(&i);
+        }
+
+        a.merge_with(&b).unwrap();
+
+        assert!(a.is_sparse());
+        assert_eq!(a.count(), 150);
+    }
+
+    #[test]
+    fn test_merge_incompatible() {
+        let mut a = HyperLogLog::<u64>::with_seed(14, 42);
+
+        assert!(matches!(
+            a.merge_with(&HyperLogLog::with_seed(12, 42)),
+            Err(FlitError::IncompatibleFilters)
+        ));
+        assert!(matches!(
+            a.merge_with(&HyperLogLog::with_seed(14, 7)),
+            Err(FlitError::IncompatibleFilters)
+        ));
+    }
+
+    #[test]
+    fn test_add_hash() {
+        let mut a = HyperLogLog::with_seed(14, 42);
+        let mut b = HyperLogLog::<&str>::with_seed(14, 42);
+
+        a.add(&"Hello, world!");
+        b.add_hash(b.hasher().hash_one("Hello, world!"));
+
+        match (&a.registers, &b.registers) {
+            (
+                Registers::Sparse { entries, buffer },
+                Registers::Sparse {
+                    entries: other_entries,
+                    buffer: other_buffer,
+                },
+            ) => assert_eq!(
+                merged_entries(entries, buffer),
+                merged_entries(other_entries, other_buffer)
+            ),
+            _ => unreachable!(),
+        }
+    }
+}
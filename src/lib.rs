@@ -1,11 +1,90 @@
 //! This crate provides a couple of simple varieties of Bloom filters:
 //!
 //! - [`BloomFilter`] is a standard Bloom filter implementation. Items can be added to the filter,
-//! but cannot be removed. It is a very space-efficient data structure.
-//! - `CountingBloomFilter` (not yet implemented) is a Counting Bloom filter implementation. Items can both be added
-//! and removed. The trade off is that it has much higher space requirements than a standard Bloom
-//! filter.
+//!   but cannot be removed. It is a very space-efficient data structure.
+//! - [`AtomicBloomFilter`] is a standard Bloom filter that can be shared between threads, and
+//!   added to concurrently without a lock.
+//! - [`CountingBloomFilter`] is a Counting Bloom filter implementation. Items can both be added
+//!   and removed. The trade off is that it has much higher space requirements than a standard Bloom
+//!   filter.
+//! - [`ScalableBloomFilter`] grows as items are added to it, so the number of items does not need
+//!   to be known in advance, while keeping the false-positive rate below a fixed bound.
+//! - [`StableBloomFilter`] continuously evicts old items, so that its false-positive rate stays
+//!   bounded for unbounded streams of items, at the cost of some false negatives.
+//! - [`SlidingWindowBloomFilter`] only remembers the items that were added to it recently, either
+//!   within a time window or within a number of items, by rotating through several Bloom filters.
+//! - [`BlockedBloomFilter`] keeps all of the bits for an item within a single cache line, which
+//!   makes queries faster at the cost of slightly more space.
+//! - [`CuckooFilter`] stores short fingerprints of its items in a cuckoo hash table. Items can be
+//!   both added and removed, and it needs less space than a Bloom filter at low false-positive
+//!   rates, but inserts fail once the filter is full.
+//! - [`QuotientFilter`] stores fingerprints of its items in a compact hash table. Items can be
+//!   both added and removed, and the filter can be resized or merged with another filter without
+//!   access to the original items.
+//! - [`BinaryFuseFilter`] is built once from a known set of items, and cannot be changed
+//!   afterwards. It needs much less space than a Bloom filter, and queries are faster.
+//! - [`SplitBlockBloomFilter`] reads and writes the split-block Bloom filters stored in Apache
+//!   Parquet files.
 //!
+//! It also provides related data structures that use the same hashing:
+//!
+//! - [`CountMinSketch`] estimates how many times each item has been added to it.
+//! - [`HyperLogLog`] estimates how many distinct items have been added to it.
+//!
+//! # Cargo features
+//!
+//! - `serde`: implements `Serialize` and `Deserialize` for [`BloomFilter`].
+//! - `rayon`: adds `BloomFilter::from_par_iter` and implements `ParallelExtend` for
+//!   [`BloomFilter`], which hash items and set their bits in parallel.
+//!
+//! [`AtomicBloomFilter`]: atomic_bloom_filter/struct.AtomicBloomFilter.html
+//! [`BinaryFuseFilter`]: binary_fuse_filter/struct.BinaryFuseFilter.html
+//! [`BlockedBloomFilter`]: blocked_bloom_filter/struct.BlockedBloomFilter.html
 //! [`BloomFilter`]: bloom_filter/struct.BloomFilter.html
+//! [`CountMinSketch`]: count_min_sketch/struct.CountMinSketch.html
+//! [`CountingBloomFilter`]: counting_bloom_filter/struct.CountingBloomFilter.html
+//! [`CuckooFilter`]: cuckoo_filter/struct.CuckooFilter.html
+//! [`HyperLogLog`]: hyper_log_log/struct.HyperLogLog.html
+//! [`QuotientFilter`]: quotient_filter/struct.QuotientFilter.html
+//! [`ScalableBloomFilter`]: scalable_bloom_filter/struct.ScalableBloomFilter.html
+//! [`SlidingWindowBloomFilter`]: sliding_window_bloom_filter/struct.SlidingWindowBloomFilter.html
+//! [`SplitBlockBloomFilter`]: split_block_bloom_filter/struct.SplitBlockBloomFilter.html
+//! [`StableBloomFilter`]: stable_bloom_filter/struct.StableBloomFilter.html
+pub mod atomic_bloom_filter;
+pub mod binary_fuse_filter;
+pub mod blocked_bloom_filter;
 pub mod bloom_filter;
+pub mod builder;
+pub mod clock;
+pub mod count_min_sketch;
+pub mod counting_bloom_filter;
+pub mod cuckoo_filter;
+pub mod error;
+pub mod fingerprint;
+pub mod hash;
+pub mod hyper_log_log;
+mod packed_vec;
+pub mod quotient_filter;
+pub mod scalable_bloom_filter;
+mod serialization;
+pub mod sliding_window_bloom_filter;
+pub mod split_block_bloom_filter;
+pub mod stable_bloom_filter;
+
+pub use atomic_bloom_filter::AtomicBloomFilter;
+pub use binary_fuse_filter::BinaryFuseFilter;
+pub use blocked_bloom_filter::BlockedBloomFilter;
 pub use bloom_filter::BloomFilter;
+pub use builder::BloomFilterBuilder;
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use count_min_sketch::CountMinSketch;
+pub use counting_bloom_filter::{CounterWidth, CountingBloomFilter};
+pub use cuckoo_filter::CuckooFilter;
+pub use error::{DecodeError, FlitError};
+pub use fingerprint::FingerprintWidth;
+pub use hyper_log_log::HyperLogLog;
+pub use quotient_filter::QuotientFilter;
+pub use scalable_bloom_filter::ScalableBloomFilter;
+pub use sliding_window_bloom_filter::SlidingWindowBloomFilter;
+pub use split_block_bloom_filter::{ParquetValue, SplitBlockBloomFilter};
+pub use stable_bloom_filter::StableBloomFilter;
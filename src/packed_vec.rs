@@ -0,0 +1,151 @@
+//! A fixed-length array of small unsigned integers, packed into `u64` words.
+//!
+//! Used for the counters of a [`CountingBloomFilter`], where storing every counter in its own byte
+//! (or larger) would waste most of the space, for the fingerprints of a [`CuckooFilter`], and for
+//! the slots of a [`QuotientFilter`].
+//!
+//! [`CountingBloomFilter`]: ../counting_bloom_filter/struct.CountingBloomFilter.html
+//! [`CuckooFilter`]: ../cuckoo_filter/struct.CuckooFilter.html
+//! [`QuotientFilter`]: ../quotient_filter/struct.QuotientFilter.html
+
+use crate::error::FlitError;
+use std::mem;
+
+const WORD_BITS: usize = 64;
+
+/// A fixed-length array of `len` unsigned integers, each `width` bits wide.
+///
+/// `width` can be anything from 1 to 64. If it does not divide 64, values may straddle two
+/// words.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PackedVec {
+    width: u32,
+    len: usize,
+    words: Vec<u64>,
+}
+
+impl PackedVec {
+    /// Creates a new `PackedVec` of `len` zeroes, each `width` bits wide.
+    pub(crate) fn new(width: u32, len: usize) -> Self {
+        Self::try_new(width, len).expect("Packed vector is too large")
+    }
+
+    /// Creates a new `PackedVec` like `new`, but returns `FlitError::CapacityOverflow` instead of
+    /// panicking if the words needed to hold `len` values cannot be allocated.
+    pub(crate) fn try_new(width: u32, len: usize) -> Result<Self, FlitError> {
+        assert!(
+            width > 0 && width <= WORD_BITS as u32,
+            "Packed value width must be between 1 and 64"
+        );
+
+        let num_words = len
+            .checked_mul(width as usize)
+            .map(|bits| bits.div_ceil(WORD_BITS))
+            .filter(|&words| words <= isize::MAX as usize / mem::size_of::<u64>())
+            .ok_or(FlitError::CapacityOverflow)?;
+
+        Ok(PackedVec {
+            width,
+            len,
+            words: vec![0; num_words],
+        })
+    }
+
+    /// The largest value that can be stored in a single slot.
+    pub(crate) fn max_value(&self) -> u64 {
+        u64::MAX >> (WORD_BITS as u32 - self.width)
+    }
+
+    /// The number of values in the array.
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    pub(crate) fn get(&self, index: usize) -> u64 {
+        let (word, shift) = self.locate(index);
+
+        let mut value = self.words[word] >> shift;
+        if shift + self.width > WORD_BITS as u32 {
+            value |= self.words[word + 1] << (WORD_BITS as u32 - shift);
+        }
+
+        value & self.max_value()
+    }
+
+    pub(crate) fn set(&mut self, index: usize, value: u64) {
+        debug_assert!(value <= self.max_value());
+
+        let (word, shift) = self.locate(index);
+        let mask = self.max_value() << shift;
+        self.words[word] = (self.words[word] & !mask) | ((value << shift) & mask);
+
+        if shift + self.width > WORD_BITS as u32 {
+            let high_shift = WORD_BITS as u32 - shift;
+            let mask = self.max_value() >> high_shift;
+            self.words[word + 1] = (self.words[word + 1] & !mask) | ((value >> high_shift) & mask);
+        }
+    }
+
+    fn locate(&self, index: usize) -> (usize, u32) {
+        assert!(index < self.len, "Index out of bounds");
+
+        let bit = index * self.width as usize;
+        (bit / WORD_BITS, (bit % WORD_BITS) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set() {
+        let mut packed = PackedVec::new(4, 33);
+
+        packed.set(0, 15);
+        packed.set(16, 7);
+        packed.set(32, 1);
+
+        assert_eq!(packed.get(0), 15);
+        assert_eq!(packed.get(1), 0);
+        assert_eq!(packed.get(16), 7);
+        assert_eq!(packed.get(32), 1);
+        assert_eq!(packed.words.len(), 3);
+    }
+
+    #[test]
+    fn test_values_straddling_words() {
+        let mut packed = PackedVec::new(13, 10);
+
+        for i in 0..10 {
+            packed.set(i, 8000 + i as u64);
+        }
+        packed.set(4, 8191);
+
+        assert_eq!(packed.get(3), 8003);
+        assert_eq!(packed.get(4), 8191);
+        assert_eq!(packed.get(5), 8005);
+        assert_eq!(packed.words.len(), 3);
+    }
+
+    #[test]
+    fn test_max_value() {
+        assert_eq!(PackedVec::new(4, 1).max_value(), 15);
+        assert_eq!(PackedVec::new(8, 1).max_value(), 255);
+        assert_eq!(PackedVec::new(13, 1).max_value(), 8191);
+        assert_eq!(PackedVec::new(16, 1).max_value(), 65535);
+        assert_eq!(PackedVec::new(64, 1).max_value(), u64::MAX);
+    }
+
+    #[test]
+    fn test_try_new_too_large() {
+        assert!(matches!(
+            PackedVec::try_new(16, usize::MAX / 8),
+            Err(FlitError::CapacityOverflow)
+        ));
+        assert!(matches!(
+            PackedVec::try_new(8, usize::MAX / 4),
+            Err(FlitError::CapacityOverflow)
+        ));
+    }
+}
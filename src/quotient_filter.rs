@@ -0,0 +1,781 @@
+//! `QuotientFilter` is an alternative to a Bloom filter that stores a `p`-bit fingerprint of each
+//! item in a compact hash table. Like a [`CountingBloomFilter`], it supports removing items, but
+//! it can also be resized and merged without access to the original items.
+//!
+//! Each fingerprint is split into a quotient of `q` bits, which selects one of `2^q` slots, and a
+//! remainder of `r = p - q` bits, which is stored in the table. Remainders with the same
+//! quotient are stored next to each other in sorted order, forming a run, and runs are shifted
+//! forward to make room for each other. Three bits of metadata per slot are enough to find the
+//! run belonging to any quotient.
+//!
+//! Since the table is effectively a sorted list of fingerprints, it can be resized by moving one
+//! bit from the remainder to the quotient, and two filters can be merged like sorted runs in a
+//! single pass over both.
+//!
+//! # References
+//! - [Don't Thrash: How to Cache Your Hash on
+//!   Flash](https://www.vldb.org/pvldb/vol5/p1627_michaelabender_vldb2012.pdf)
+//! - [Wikipedia article](https://en.wikipedia.org/wiki/Quotient_filter)
+//!
+//! [`CountingBloomFilter`]: ../counting_bloom_filter/struct.CountingBloomFilter.html
+
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use crate::packed_vec::PackedVec;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// Set on a slot if some item has this slot's index as its quotient.
+const OCCUPIED: u64 = 1;
+
+/// Set on a slot if its remainder belongs to the same run as the previous slot.
+const CONTINUATION: u64 = 2;
+
+/// Set on a slot if its remainder is not stored in its canonical slot.
+const SHIFTED: u64 = 4;
+
+/// The number of metadata bits stored in each slot, in front of the remainder.
+const METADATA_BITS: u32 = 3;
+
+/// The fraction of slots that `new` and `merge` size the filter for. Lookups slow down as the
+/// table fills up, since runs are shifted further away from their canonical slots.
+const MAX_LOAD_FACTOR: f64 = 0.75;
+
+/// Represents a Quotient filter.
+///
+/// When constructing the filter using `new`, you need to specify the desired acceptable
+/// false-positive rate, and the number of items you intend to store in the filter. The filter
+/// can later be doubled in size using `grow`, at the cost of one bit of each remainder, which
+/// doubles the false-positive rate.
+///
+/// # Example
+/// ```rust
+/// use flit::QuotientFilter;
+///
+/// let mut filter = QuotientFilter::new(0.01, 10000);
+/// filter.insert(&"Hello, world!").unwrap();
+///
+/// assert_eq!(filter.contains(&"Hello, world!"), true); // probably true
+/// assert_eq!(filter.contains(&"Dogs are cool!"), false); // definitely false!
+///
+/// filter.grow().unwrap();
+/// assert_eq!(filter.contains(&"Hello, world!"), true);
+///
+/// assert_eq!(filter.remove(&"Hello, world!"), true);
+/// assert_eq!(filter.contains(&"Hello, world!"), false);
+/// ```
+pub struct QuotientFilter<T, S = XxHashBuilder> {
+    n: u64,
+    q: u32,
+    r: u32,
+    slots: PackedVec,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Hash> QuotientFilter<T> {
+    /// Creates a new Quotient filter based on the required false positive rate and the estimated
+    /// number of items that will be added to the filter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate.
+    pub fn new(false_positive_rate: f64, estimated_items: usize) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new Quotient filter like `new`, but hashes items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate.
+    pub fn with_seed(false_positive_rate: f64, estimated_items: usize, seed: u64) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            estimated_items,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> QuotientFilter<T, S> {
+    /// Creates a new Quotient filter like `new`, but hashes items using the given
+    /// `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `estimated_items` is not greater than 0, or if the filter would be too large to
+    /// allocate. Use `try_with_hasher` to handle these cases without panicking.
+    pub fn with_hasher(false_positive_rate: f64, estimated_items: usize, build_hasher: S) -> Self {
+        Self::try_with_hasher(false_positive_rate, estimated_items, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Quotient filter like `with_hasher`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_hasher(
+        false_positive_rate: f64,
+        estimated_items: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        if !(false_positive_rate > 0_f64 && false_positive_rate < 1_f64) {
+            return Err(FlitError::InvalidFalsePositiveRate(false_positive_rate));
+        }
+        if estimated_items == 0 {
+            return Err(FlitError::ZeroCapacity);
+        }
+
+        // At most a fraction `2^-r` of the possible remainders match in each run, and at most one
+        // run is checked for each lookup.
+        let q = ((estimated_items as f64 / MAX_LOAD_FACTOR).log2().ceil() as u32).max(1);
+        let r = ((1_f64 / false_positive_rate).log2().ceil() as u32).max(1);
+
+        Self::with_parts(q, r, build_hasher)
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Inserts the `item` into the filter.
+    ///
+    /// The same item can be inserted more than once, in which case it has to be removed just as
+    /// many times before the filter stops reporting it.
+    ///
+    /// Returns `FlitError::FilterFull`, leaving the filter unchanged, if there is no room left
+    /// for the item. Use `grow` to make room for more items.
+    pub fn insert(&mut self, item: &T) -> Result<(), FlitError> {
+        let (quotient, remainder) = self.quotient_and_remainder(self.fingerprint(item));
+        self.insert_fingerprint(quotient, remainder)
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely does not contain the item.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn contains(&self, item: &T) -> bool {
+        let (quotient, remainder) = self.quotient_and_remainder(self.fingerprint(item));
+        if !self.is_occupied(quotient) {
+            return false;
+        }
+
+        let mut slot = self.find_run_start(quotient);
+        loop {
+            let stored = self.remainder(slot);
+            if stored >= remainder {
+                return stored == remainder;
+            }
+
+            slot = self.next(slot);
+            if !self.is_continuation(slot) {
+                return false;
+            }
+        }
+    }
+
+    /// Removes the `item` from the filter.
+    ///
+    /// Returns `false`, leaving the filter unchanged, if the item is definitely not in the
+    /// filter. Otherwise, returns `true`.
+    ///
+    /// Note that removing an item that was never added, but for which the filter reports a
+    /// false-positive, will introduce false negatives for other items.
+    pub fn remove(&mut self, item: &T) -> bool {
+        let (quotient, remainder) = self.quotient_and_remainder(self.fingerprint(item));
+        if !self.is_occupied(quotient) {
+            return false;
+        }
+
+        let run_start = self.find_run_start(quotient);
+        let mut slot = run_start;
+        loop {
+            let stored = self.remainder(slot);
+            if stored == remainder {
+                break;
+            }
+            if stored > remainder {
+                return false;
+            }
+
+            slot = self.next(slot);
+            if !self.is_continuation(slot) {
+                return false;
+            }
+        }
+
+        let is_run_start = slot == run_start;
+        let run_continues = self.is_continuation(self.next(slot));
+        if is_run_start && !run_continues {
+            self.set_occupied(quotient, false);
+        }
+
+        // Shift the rest of the cluster back by one slot, until reaching an empty slot or a
+        // remainder that is already in its canonical slot.
+        let mut run_quotient = quotient;
+        let mut promote_to_run_start = is_run_start && run_continues;
+        loop {
+            let next = self.next(slot);
+            if self.is_empty_slot(next) || !self.is_shifted(next) {
+                self.write_entry(slot, 0, false, false);
+                break;
+            }
+
+            let (remainder, continuation) = (self.remainder(next), self.is_continuation(next));
+            if !continuation {
+                run_quotient = self.next_occupied(run_quotient);
+            }
+
+            self.write_entry(
+                slot,
+                remainder,
+                continuation && !promote_to_run_start,
+                slot != run_quotient,
+            );
+            promote_to_run_start = false;
+            slot = next;
+        }
+
+        self.n -= 1;
+        true
+    }
+
+    /// Returns the `p`-bit fingerprint of `item`.
+    fn fingerprint(&self, item: &T) -> u64 {
+        self.build_hasher.hash_one(item) >> (64 - (self.q + self.r))
+    }
+}
+
+impl<T, S> QuotientFilter<T, S> {
+    /// Creates an empty filter with `2^q` slots and `r`-bit remainders.
+    fn with_parts(q: u32, r: u32, build_hasher: S) -> Result<Self, FlitError> {
+        if q + r > 64 || r + METADATA_BITS > 64 {
+            return Err(FlitError::CapacityOverflow);
+        }
+
+        let num_slots = 1_usize
+            .checked_shl(q)
+            .filter(|slots| slots.checked_mul((r + METADATA_BITS) as usize).is_some())
+            .ok_or(FlitError::CapacityOverflow)?;
+
+        Ok(QuotientFilter {
+            n: 0,
+            q,
+            r,
+            slots: PackedVec::new(r + METADATA_BITS, num_slots),
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns the number of items in the filter.
+    pub fn len(&self) -> u64 {
+        self.n
+    }
+
+    /// Returns `true` if the filter holds no items.
+    pub fn is_empty(&self) -> bool {
+        self.n == 0
+    }
+
+    /// Returns the number of slots in the filter, which is `2^q`.
+    ///
+    /// One slot is always kept empty, so the filter can hold one item less than this.
+    pub fn num_slots(&self) -> u64 {
+        self.slots.len() as u64
+    }
+
+    /// Returns the number of bits in the quotient of each fingerprint (`q`).
+    pub fn quotient_bits(&self) -> u32 {
+        self.q
+    }
+
+    /// Returns the number of bits in the remainder of each fingerprint (`r`).
+    pub fn remainder_bits(&self) -> u32 {
+        self.r
+    }
+
+    /// Returns the fraction of slots in the filter that hold a remainder.
+    pub fn load_factor(&self) -> f64 {
+        self.n as f64 / self.num_slots() as f64
+    }
+
+    /// Calculates the current expected false positive rate given the number of items in the
+    /// filter.
+    pub fn false_positive_rate(&self) -> f64 {
+        1_f64 - (-self.load_factor() / 2_f64.powi(self.r as i32)).exp()
+    }
+
+    /// Doubles the number of slots in the filter, by moving one bit of each fingerprint from the
+    /// remainder to the quotient.
+    ///
+    /// This halves the load factor, but doubles the false-positive rate of the filter.
+    ///
+    /// Returns `FlitError::CapacityOverflow`, leaving the filter unchanged, if the remainders
+    /// only have a single bit left, or if the filter would be too large to allocate.
+    pub fn grow(&mut self) -> Result<(), FlitError> {
+        if self.r <= 1 {
+            return Err(FlitError::CapacityOverflow);
+        }
+
+        let fingerprints = self.sorted_fingerprints();
+        let (q, r) = (self.q + 1, self.r - 1);
+        let num_slots = 1_usize
+            .checked_shl(q)
+            .filter(|slots| slots.checked_mul((r + METADATA_BITS) as usize).is_some())
+            .ok_or(FlitError::CapacityOverflow)?;
+
+        self.q = q;
+        self.r = r;
+        self.slots = PackedVec::new(r + METADATA_BITS, num_slots);
+        self.n = 0;
+        self.extend_sorted(fingerprints.into_iter())
+            .expect("a filter with twice as many slots always has enough room");
+
+        Ok(())
+    }
+
+    /// Returns a new filter that contains the items of both filters.
+    ///
+    /// Both filters must use fingerprints of the same length (`q + r`) and the same hasher. The
+    /// merged filter has at least as many slots as the larger of the two, and is doubled in size
+    /// (shortening its remainders) until the combined items fit.
+    ///
+    /// Both filters are read in a single pass, in the order of their fingerprints, so merging
+    /// takes linear time.
+    ///
+    /// # Example
+    /// ```rust
+    /// use flit::QuotientFilter;
+    ///
+    /// let mut a = QuotientFilter::with_seed(0.01, 1000, 42);
+    /// let mut b = QuotientFilter::with_seed(0.01, 1000, 42);
+    /// a.insert(&"Hello, world!").unwrap();
+    /// b.insert(&"Dogs are cool!").unwrap();
+    ///
+    /// let merged = a.merge(&b).unwrap();
+    ///
+    /// assert_eq!(merged.contains(&"Hello, world!"), true);
+    /// assert_eq!(merged.contains(&"Dogs are cool!"), true);
+    /// ```
+    pub fn merge(&self, other: &Self) -> Result<Self, FlitError>
+    where
+        S: Clone + PartialEq,
+    {
+        let p = self.q + self.r;
+        if other.q + other.r != p || self.build_hasher != other.build_hasher {
+            return Err(FlitError::IncompatibleFilters);
+        }
+
+        let n = self.n + other.n;
+        let mut q = self.q.max(other.q);
+        while n as f64 > MAX_LOAD_FACTOR * 2_f64.powi(q as i32) {
+            q += 1;
+        }
+        if q >= p {
+            return Err(FlitError::CapacityOverflow);
+        }
+
+        let mut merged = Self::with_parts(q, p - q, self.build_hasher.clone())?;
+        let mut a = self.sorted_fingerprints().into_iter().peekable();
+        let mut b = other.sorted_fingerprints().into_iter().peekable();
+        let fingerprints = std::iter::from_fn(|| match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) if x <= y => a.next(),
+            (Some(_), Some(_)) => b.next(),
+            (Some(_), None) => a.next(),
+            (None, _) => b.next(),
+        });
+        merged.extend_sorted(fingerprints)?;
+
+        Ok(merged)
+    }
+
+    /// Splits a fingerprint into its quotient, which is the index of its canonical slot, and its
+    /// remainder.
+    fn quotient_and_remainder(&self, fingerprint: u64) -> (usize, u64) {
+        let quotient = (fingerprint >> self.r) as usize;
+        let remainder = fingerprint & (self.slots.max_value() >> METADATA_BITS);
+
+        (quotient, remainder)
+    }
+
+    /// Inserts a fingerprint, keeping the remainders of its run in sorted order.
+    fn insert_fingerprint(&mut self, quotient: usize, remainder: u64) -> Result<(), FlitError> {
+        if self.n + 1 >= self.num_slots() {
+            return Err(FlitError::FilterFull);
+        }
+        self.n += 1;
+
+        if self.is_empty_slot(quotient) {
+            self.slots
+                .set(quotient, (remainder << METADATA_BITS) | OCCUPIED);
+            return Ok(());
+        }
+
+        // Marking the quotient as occupied first makes `find_run_start` return the slot where a
+        // new run has to be started, if there was no run for it yet.
+        let run_exists = self.is_occupied(quotient);
+        self.set_occupied(quotient, true);
+        let run_start = self.find_run_start(quotient);
+
+        let mut slot = run_start;
+        if run_exists {
+            while self.remainder(slot) < remainder {
+                slot = self.next(slot);
+                if !self.is_continuation(slot) {
+                    break;
+                }
+            }
+        }
+
+        // Shift every following remainder in the cluster forward by one slot. If the new
+        // remainder becomes the start of an existing run, the old start becomes a continuation.
+        let mut entry = (remainder, slot != run_start, slot != quotient);
+        let mut demote_run_start = run_exists && slot == run_start;
+        loop {
+            let was_empty = self.is_empty_slot(slot);
+            let previous = (self.remainder(slot), self.is_continuation(slot));
+            self.write_entry(slot, entry.0, entry.1, entry.2);
+            if was_empty {
+                return Ok(());
+            }
+
+            entry = (previous.0, previous.1 || demote_run_start, true);
+            demote_run_start = false;
+            slot = self.next(slot);
+        }
+    }
+
+    /// Adds fingerprints, which must be given in sorted order, to an empty filter.
+    ///
+    /// Since every fingerprint is greater than or equal to the ones before it, it can be written
+    /// directly after them without shifting anything. Only fingerprints whose cluster wraps
+    /// around the end of the table are inserted the regular way.
+    fn extend_sorted(&mut self, fingerprints: impl Iterator<Item = u64>) -> Result<(), FlitError> {
+        let mut last: Option<(usize, usize)> = None;
+        let mut wrapped = false;
+
+        for fingerprint in fingerprints {
+            let (quotient, remainder) = self.quotient_and_remainder(fingerprint);
+            let slot = last.map_or(quotient, |(slot, _)| quotient.max(slot + 1));
+
+            wrapped |= slot >= self.slots.len();
+            if wrapped {
+                self.insert_fingerprint(quotient, remainder)?;
+                continue;
+            }
+            if self.n + 1 >= self.num_slots() {
+                return Err(FlitError::FilterFull);
+            }
+
+            let continuation = last.is_some_and(|(_, last_quotient)| last_quotient == quotient);
+            self.write_entry(slot, remainder, continuation, slot != quotient);
+            self.set_occupied(quotient, true);
+            self.n += 1;
+            last = Some((slot, quotient));
+        }
+
+        Ok(())
+    }
+
+    /// Returns all of the fingerprints in the filter in sorted order.
+    fn sorted_fingerprints(&self) -> Vec<u64> {
+        let mut fingerprints = Vec::with_capacity(self.n as usize);
+
+        // Start right after an empty slot, where a new cluster begins, so that every run can be
+        // matched up with its quotient.
+        let start = (0..self.slots.len())
+            .find(|&slot| self.is_empty_slot(slot))
+            .expect("one slot is always kept empty");
+        let mut quotient = start;
+        let mut slot = start;
+        for _ in 1..self.slots.len() {
+            slot = self.next(slot);
+            if self.is_empty_slot(slot) {
+                continue;
+            }
+            if !self.is_continuation(slot) {
+                quotient = self.next_occupied(quotient);
+            }
+
+            fingerprints.push(((quotient as u64) << self.r) | self.remainder(slot));
+        }
+
+        // The fingerprints are sorted, except that the ones whose quotient comes before `start`
+        // were visited last.
+        if let Some(wrap) = (1..fingerprints.len()).find(|&i| fingerprints[i] < fingerprints[i - 1])
+        {
+            fingerprints.rotate_left(wrap);
+        }
+
+        fingerprints
+    }
+
+    /// Returns the slot holding the first remainder of the run for `quotient`, which must be
+    /// occupied.
+    fn find_run_start(&self, quotient: usize) -> usize {
+        // Walk back to the start of the cluster, which is always in its canonical slot.
+        let mut canonical = quotient;
+        while self.is_shifted(canonical) {
+            canonical = self.previous(canonical);
+        }
+
+        // Walk forward, skipping one run for every occupied slot until reaching `quotient`.
+        let mut slot = canonical;
+        while canonical != quotient {
+            slot = self.next(slot);
+            while self.is_continuation(slot) {
+                slot = self.next(slot);
+            }
+            canonical = self.next_occupied(canonical);
+        }
+
+        slot
+    }
+
+    /// Returns the first occupied slot after `slot`.
+    fn next_occupied(&self, mut slot: usize) -> usize {
+        slot = self.next(slot);
+        while !self.is_occupied(slot) {
+            slot = self.next(slot);
+        }
+
+        slot
+    }
+
+    fn next(&self, slot: usize) -> usize {
+        (slot + 1) & (self.slots.len() - 1)
+    }
+
+    fn previous(&self, slot: usize) -> usize {
+        slot.wrapping_sub(1) & (self.slots.len() - 1)
+    }
+
+    fn remainder(&self, slot: usize) -> u64 {
+        self.slots.get(slot) >> METADATA_BITS
+    }
+
+    fn is_occupied(&self, slot: usize) -> bool {
+        self.slots.get(slot) & OCCUPIED != 0
+    }
+
+    fn is_continuation(&self, slot: usize) -> bool {
+        self.slots.get(slot) & CONTINUATION != 0
+    }
+
+    fn is_shifted(&self, slot: usize) -> bool {
+        self.slots.get(slot) & SHIFTED != 0
+    }
+
+    /// A slot is empty if none of its metadata bits are set. An occupied slot is never empty,
+    /// since its run either starts there, or has pushed another remainder into it.
+    fn is_empty_slot(&self, slot: usize) -> bool {
+        self.slots.get(slot) & (OCCUPIED | CONTINUATION | SHIFTED) == 0
+    }
+
+    fn set_occupied(&mut self, slot: usize, occupied: bool) {
+        let value = self.slots.get(slot) & !OCCUPIED;
+        self.slots
+            .set(slot, value | if occupied { OCCUPIED } else { 0 });
+    }
+
+    /// Stores a remainder and its metadata in `slot`, keeping the slot's `OCCUPIED` bit.
+    fn write_entry(&mut self, slot: usize, remainder: u64, continuation: bool, shifted: bool) {
+        let mut value = (remainder << METADATA_BITS) | (self.slots.get(slot) & OCCUPIED);
+        if continuation {
+            value |= CONTINUATION;
+        }
+        if shifted {
+            value |= SHIFTED;
+        }
+
+        self.slots.set(slot, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_num_slots() {
+        let filter = QuotientFilter::<u32>::new(0.01_f64, 1000);
+
+        // 1000 / 0.75 = 1334 slots, rounded up to a power of two.
+        assert_eq!(filter.num_slots(), 2048);
+        assert_eq!(filter.quotient_bits(), 11);
+        assert_eq!(filter.remainder_bits(), 7);
+    }
+
+    #[test]
+    fn test_try_with_hasher() {
+        assert!(matches!(
+            QuotientFilter::<u32>::try_with_hasher(0.01_f64, 0, XxHashBuilder::with_seed(42)),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(matches!(
+            QuotientFilter::<u32>::try_with_hasher(1e-300_f64, 100, XxHashBuilder::with_seed(42)),
+            Err(FlitError::CapacityOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut filter = QuotientFilter::with_seed(0.03_f64, 10, 42);
+
+        filter.insert(&"Hello, world!").unwrap();
+        filter.insert(&"Hello, world!").unwrap();
+
+        assert_eq!(filter.len(), 2);
+        assert!(filter.false_positive_rate() > 0.0);
+        assert!(filter.contains(&"Hello, world!"));
+        assert!(!filter.contains(&"Dogs are cool!"));
+
+        assert!(!filter.remove(&"Dogs are cool!"));
+        assert!(filter.remove(&"Hello, world!"));
+        assert!(filter.contains(&"Hello, world!"));
+        assert!(filter.remove(&"Hello, world!"));
+        assert!(!filter.contains(&"Hello, world!"));
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn test_insert_fails_when_full() {
+        let mut filter = QuotientFilter::with_seed(0.01_f64, 3, 42);
+
+        for i in 0..filter.num_slots() - 1 {
+            filter.insert(&i).unwrap();
+        }
+
+        assert!(matches!(filter.insert(&0), Err(FlitError::FilterFull)));
+        assert!((0..filter.num_slots() - 1).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn test_random_operations() {
+        // Short fingerprints produce long clusters, with many collisions and runs that wrap
+        // around the end of the table.
+        let mut filter = QuotientFilter::with_parts(6, 2, XxHashBuilder::with_seed(42)).unwrap();
+        let mut counts = HashMap::new();
+
+        let mut state = 42_u64;
+        for _ in 0..20_000 {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            let item = (state >> 33) % 100;
+
+            if filter.load_factor() < 0.9 && state >> 63 == 0 {
+                filter.insert(&item).unwrap();
+                *counts.entry(item).or_insert(0) += 1;
+            } else if counts.get(&item).copied().unwrap_or(0) > 0 {
+                assert!(filter.remove(&item));
+                *counts.get_mut(&item).unwrap() -= 1;
+            }
+
+            for (item, &count) in &counts {
+                assert!(count == 0 || filter.contains(item));
+            }
+        }
+
+        for (item, count) in counts {
+            for _ in 0..count {
+                assert!(filter.remove(&item));
+            }
+        }
+        assert!(filter.is_empty());
+        assert!((0..filter.slots.len()).all(|slot| filter.slots.get(slot) == 0));
+    }
+
+    #[test]
+    fn test_grow() {
+        let mut filter = QuotientFilter::with_seed(0.01_f64, 1000, 42);
+        for i in 0..1000 {
+            filter.insert(&i).unwrap();
+        }
+
+        filter.grow().unwrap();
+
+        assert_eq!(filter.num_slots(), 4096);
+        assert_eq!(filter.remainder_bits(), 6);
+        assert_eq!(filter.len(), 1000);
+        assert!((0..1000).all(|i| filter.contains(&i)));
+
+        for i in 1000..3000 {
+            filter.insert(&i).unwrap();
+        }
+        assert!((0..3000).all(|i| filter.contains(&i)));
+    }
+
+    #[test]
+    fn test_grow_and_merge_full_tables() {
+        // Nearly full tables have clusters that wrap around the end of the table.
+        for seed in 0..100 {
+            let mut a = QuotientFilter::with_parts(4, 8, XxHashBuilder::with_seed(seed)).unwrap();
+            let mut b = QuotientFilter::with_parts(4, 8, XxHashBuilder::with_seed(seed)).unwrap();
+            for i in 0..15 {
+                a.insert(&i).unwrap();
+                b.insert(&(i + 15)).unwrap();
+            }
+
+            let merged = a.merge(&b).unwrap();
+            assert_eq!(merged.num_slots(), 64);
+            assert!((0..30).all(|i| merged.contains(&i)));
+
+            a.grow().unwrap();
+            assert!((0..15).all(|i| a.contains(&i)));
+        }
+    }
+
+    #[test]
+    fn test_grow_fails_without_remainder_bits() {
+        let mut filter =
+            QuotientFilter::<u32>::with_parts(4, 1, XxHashBuilder::with_seed(42)).unwrap();
+
+        assert!(matches!(filter.grow(), Err(FlitError::CapacityOverflow)));
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = QuotientFilter::with_seed(0.01_f64, 1000, 42);
+        let mut b = QuotientFilter::with_seed(0.01_f64, 1000, 42);
+        for i in 0..1000 {
+            a.insert(&i).unwrap();
+            b.insert(&(i + 500)).unwrap();
+        }
+
+        let mut merged = a.merge(&b).unwrap();
+
+        assert_eq!(merged.len(), 2000);
+        assert_eq!(merged.num_slots(), 4096);
+        assert!((0..1500).all(|i| merged.contains(&i)));
+        assert!(merged.remove(&700));
+        assert!(merged.contains(&700));
+    }
+
+    #[test]
+    fn test_merge_incompatible() {
+        let a = QuotientFilter::<u32>::with_seed(0.01_f64, 1000, 42);
+        let b = QuotientFilter::<u32>::with_seed(0.01_f64, 1000, 43);
+        let c = QuotientFilter::<u32>::with_seed(0.001_f64, 1000, 42);
+
+        assert!(matches!(a.merge(&b), Err(FlitError::IncompatibleFilters)));
+        assert!(matches!(a.merge(&c), Err(FlitError::IncompatibleFilters)));
+    }
+}
@@ -0,0 +1,378 @@
+//! `ScalableBloomFilter` is a Bloom filter that grows as items are added to it, so that the number
+//! of items does not need to be known in advance.
+//!
+//! The filter is made up of a series of [`BloomFilter`] slices. Once the newest slice has reached
+//! its capacity, a new slice is added which can hold `growth_factor` times as many items, at a
+//! false-positive rate `tightening_ratio` times lower. This keeps the false-positive rate of the
+//! whole filter below the requested rate, no matter how many items are added.
+//!
+//! # References
+//! - [Scalable Bloom Filters](https://gsd.di.uminho.pt/members/cbm/ps/dbloom.pdf)
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+
+use crate::bloom_filter::{optimal_parameters, BloomFilter};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+
+/// The default factor by which the capacity of each slice grows.
+const DEFAULT_GROWTH_FACTOR: u32 = 2;
+
+/// The default factor by which the false-positive rate of each slice shrinks.
+const DEFAULT_TIGHTENING_RATIO: f64 = 0.85;
+
+/// Represents a Scalable Bloom filter.
+///
+/// When constructing the filter using `new`, you need to specify the desired acceptable
+/// false-positive rate, and the number of items the first slice of the filter should hold. The
+/// filter grows automatically once more items are added.
+///
+/// # Example
+/// ```rust
+/// use flit::ScalableBloomFilter;
+///
+/// let mut filter = ScalableBloomFilter::new(0.01, 100);
+/// for i in 0..10000 {
+///     filter.add(&i);
+/// }
+///
+/// assert_eq!(filter.might_contain(&42), true); // probably true
+/// assert_eq!(filter.might_contain(&-1), false); // definitely false!
+/// assert!(filter.false_positive_rate() < 0.01);
+/// ```
+pub struct ScalableBloomFilter<T, S = XxHashBuilder> {
+    slices: Vec<BloomFilter<T, S>>,
+    false_positive_rate: f64,
+    initial_capacity: usize,
+    growth_factor: u32,
+    tightening_ratio: f64,
+    build_hasher: S,
+}
+
+impl<T: Hash> ScalableBloomFilter<T> {
+    /// Creates a new Scalable Bloom filter based on the required false positive rate and the
+    /// number of items that the first slice of the filter will hold.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `initial_capacity` is not greater than 0, or if the first slice would be too large to
+    /// allocate. Use `try_new` to handle these cases without panicking.
+    pub fn new(false_positive_rate: f64, initial_capacity: usize) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            initial_capacity,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new Scalable Bloom filter like `new`, but hashes items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `initial_capacity` is not greater than 0, or if the first slice would be too large to
+    /// allocate. Use `try_with_seed` to handle these cases without panicking.
+    pub fn with_seed(false_positive_rate: f64, initial_capacity: usize, seed: u64) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            initial_capacity,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Creates a new Scalable Bloom filter like `new`, but returns an error instead of panicking
+    /// if the parameters are invalid.
+    pub fn try_new(false_positive_rate: f64, initial_capacity: usize) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            initial_capacity,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new Scalable Bloom filter like `with_seed`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_seed(
+        false_positive_rate: f64,
+        initial_capacity: usize,
+        seed: u64,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            initial_capacity,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> ScalableBloomFilter<T, S> {
+    /// Creates a new Scalable Bloom filter like `new`, but hashes items using the given
+    /// `build_hasher`. Every slice of the filter uses a copy of `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `initial_capacity` is not greater than 0, or if the first slice would be too large to
+    /// allocate. Use `try_with_hasher` to handle these cases without panicking.
+    pub fn with_hasher(false_positive_rate: f64, initial_capacity: usize, build_hasher: S) -> Self {
+        Self::try_with_hasher(false_positive_rate, initial_capacity, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Scalable Bloom filter like `with_hasher`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_with_hasher(
+        false_positive_rate: f64,
+        initial_capacity: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        optimal_parameters(false_positive_rate, initial_capacity)?;
+
+        Ok(ScalableBloomFilter {
+            slices: Vec::new(),
+            false_positive_rate,
+            initial_capacity,
+            growth_factor: DEFAULT_GROWTH_FACTOR,
+            tightening_ratio: DEFAULT_TIGHTENING_RATIO,
+            build_hasher,
+        })
+    }
+
+    /// Sets the factor by which the capacity of each new slice grows. Defaults to 2.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `growth_factor` is less than 1, or if items have already been
+    /// added to the filter.
+    pub fn growth_factor(mut self, growth_factor: u32) -> Self {
+        assert!(growth_factor >= 1, "Growth factor must be at least 1");
+        assert!(
+            self.slices.is_empty(),
+            "Growth factor cannot be changed after items have been added"
+        );
+
+        self.growth_factor = growth_factor;
+        self
+    }
+
+    /// Sets the factor by which the false-positive rate of each new slice shrinks. Defaults to
+    /// 0.85.
+    ///
+    /// Lower ratios make the filter grow more slowly, but need more bits for each slice.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `tightening_ratio` is not between 0 and 1 (non inclusive), or
+    /// if items have already been added to the filter.
+    pub fn tightening_ratio(mut self, tightening_ratio: f64) -> Self {
+        assert!(
+            tightening_ratio > 0_f64 && tightening_ratio < 1_f64,
+            "Tightening ratio must be between 0 and 1 (non-inclusive)"
+        );
+        assert!(
+            self.slices.is_empty(),
+            "Tightening ratio cannot be changed after items have been added"
+        );
+
+        self.tightening_ratio = tightening_ratio;
+        self
+    }
+
+    /// Adds the `item` to the newest slice of the filter, adding a new slice first if the newest
+    /// slice is full.
+    ///
+    /// Items that the filter might already contain are not added again, so that adding the same
+    /// item repeatedly does not use up the capacity of the filter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if a new slice is needed, but it would be too large to allocate.
+    /// Use `try_add` to handle this case without panicking.
+    pub fn add(&mut self, item: &T) {
+        self.try_add(item)
+            .unwrap_or_else(|err| panic!("Failed to grow filter: {}", err));
+    }
+
+    /// Adds the `item` to the filter like `add`, but returns `FlitError::CapacityOverflow`,
+    /// leaving the filter unchanged, if the filter needs to grow and cannot.
+    pub fn try_add(&mut self, item: &T) -> Result<(), FlitError> {
+        if self.might_contain(item) {
+            return Ok(());
+        }
+
+        let is_full = match self.slices.last() {
+            Some(slice) => slice.len() >= self.slice_capacity(self.slices.len() - 1) as u64,
+            None => true,
+        };
+        if is_full {
+            self.add_slice()?;
+        }
+
+        self.slices
+            .last_mut()
+            .expect("filter should have at least one slice")
+            .add(item);
+
+        Ok(())
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely does not contain the item.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        // Most items are in the largest slice, so check the newest slices first.
+        self.slices
+            .iter()
+            .rev()
+            .any(|slice| slice.might_contain(item))
+    }
+
+    /// Returns the number of items that have been added to the filter, not counting items that
+    /// the filter might already have contained when they were added.
+    pub fn len(&self) -> u64 {
+        self.slices.iter().map(BloomFilter::len).sum()
+    }
+
+    /// Returns `true` if no items have been added to the filter.
+    pub fn is_empty(&self) -> bool {
+        self.slices.is_empty()
+    }
+
+    /// Returns the number of slices in the filter.
+    pub fn num_slices(&self) -> usize {
+        self.slices.len()
+    }
+
+    /// Calculates the current expected false positive rate given the number of items in each
+    /// slice of the filter.
+    pub fn false_positive_rate(&self) -> f64 {
+        1_f64
+            - self
+                .slices
+                .iter()
+                .map(|slice| 1_f64 - slice.false_positive_rate())
+                .product::<f64>()
+    }
+
+    /// The number of items that the slice at `index` can hold.
+    fn slice_capacity(&self, index: usize) -> usize {
+        u32::try_from(index)
+            .ok()
+            .and_then(|index| (self.growth_factor as usize).checked_pow(index))
+            .and_then(|growth| growth.checked_mul(self.initial_capacity))
+            .unwrap_or(usize::MAX)
+    }
+
+    fn add_slice(&mut self) -> Result<(), FlitError> {
+        let index = self.slices.len();
+
+        // The false-positive rates of the slices form a geometric series, which adds up to at
+        // most `false_positive_rate`.
+        let false_positive_rate = self.false_positive_rate
+            * (1_f64 - self.tightening_ratio)
+            * self.tightening_ratio.powi(index as i32);
+
+        // Once the rate of a new slice underflows to zero, the filter cannot grow any further
+        // either.
+        let slice = BloomFilter::try_with_hasher(
+            false_positive_rate,
+            self.slice_capacity(index),
+            self.build_hasher.clone(),
+        )
+        .map_err(|_| FlitError::CapacityOverflow)?;
+
+        self.slices.push(slice);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_new() {
+        assert!(matches!(
+            ScalableBloomFilter::<&str>::try_new(1.5_f64, 10),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            ScalableBloomFilter::<&str>::try_new(0.01_f64, 0),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(ScalableBloomFilter::<&str>::try_with_seed(0.01_f64, 10, 42).is_ok());
+    }
+
+    #[test]
+    fn test_try_add_cannot_grow() {
+        // The first slice holds `initial_capacity` items at a lower rate than the whole filter,
+        // so it needs more bits than the parameters that were validated.
+        let mut filter = ScalableBloomFilter::try_new(0.01_f64, usize::MAX / 100).unwrap();
+
+        assert!(matches!(
+            filter.try_add(&"Hello, world!"),
+            Err(FlitError::CapacityOverflow)
+        ));
+        assert!(filter.is_empty());
+    }
+
+    #[test]
+    fn test_grows() {
+        let mut filter = ScalableBloomFilter::new(0.01_f64, 100);
+
+        assert!(filter.is_empty());
+        assert!(!filter.might_contain(&0));
+
+        for i in 0..100 {
+            filter.add(&i);
+        }
+        assert_eq!(filter.num_slices(), 1);
+
+        for i in 100..1000 {
+            filter.add(&i);
+        }
+
+        // 100 + 200 + 400 + 800 >= 1000
+        assert_eq!(filter.num_slices(), 4);
+        assert!((0..1000).all(|i| filter.might_contain(&i)));
+        assert!(filter.false_positive_rate() < 0.01);
+    }
+
+    #[test]
+    fn test_false_positive_rate_bound() {
+        let mut filter = ScalableBloomFilter::with_seed(0.01_f64, 100, 42).growth_factor(4);
+
+        for i in 0..100_000 {
+            filter.add(&i);
+        }
+
+        let false_positives = (100_000..200_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+
+        assert!(filter.false_positive_rate() < 0.01);
+        assert!(false_positives < 1000);
+    }
+
+    #[test]
+    fn test_duplicates_do_not_use_capacity() {
+        let mut filter = ScalableBloomFilter::new(0.01_f64, 10);
+
+        for _ in 0..100 {
+            filter.add(&"Hello, world!");
+        }
+
+        assert_eq!(filter.len(), 1);
+        assert_eq!(filter.num_slices(), 1);
+    }
+}
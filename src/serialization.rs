@@ -0,0 +1,111 @@
+//! Helpers for reading and writing the binary format used to persist filters.
+//!
+//! All integers are stored in little-endian byte order. Every serialized filter ends with an
+//! xxHash64 checksum (seeded with 0) of all of the bytes that precede it.
+
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use twox_hash::XxHash64;
+
+/// Identifies the [`XxHashBuilder`] hasher in a serialized filter.
+///
+/// [`XxHashBuilder`]: ../hash/struct.XxHashBuilder.html
+pub(crate) const HASHER_XXHASH64: u8 = 1;
+
+/// A writer that keeps a running checksum of everything written through it.
+pub(crate) struct ChecksumWriter<W> {
+    inner: W,
+    hasher: XxHash64,
+}
+
+impl<W: Write> ChecksumWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        ChecksumWriter {
+            inner,
+            hasher: XxHash64::with_seed(0),
+        }
+    }
+
+    pub(crate) fn write_u8(&mut self, value: u8) -> io::Result<()> {
+        self.write_all(&[value])
+    }
+
+    pub(crate) fn write_u32(&mut self, value: u32) -> io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+
+    pub(crate) fn write_u64(&mut self, value: u64) -> io::Result<()> {
+        self.write_all(&value.to_le_bytes())
+    }
+
+    /// Writes the checksum of everything written so far, which is not itself included in the
+    /// checksum.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        let checksum = self.hasher.finish();
+        self.inner.write_all(&checksum.to_le_bytes())?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.write(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that keeps a running checksum of everything read through it.
+pub(crate) struct ChecksumReader<R> {
+    inner: R,
+    hasher: XxHash64,
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        ChecksumReader {
+            inner,
+            hasher: XxHash64::with_seed(0),
+        }
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0; N];
+        self.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub(crate) fn read_u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.read_array()?))
+    }
+
+    pub(crate) fn read_u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.read_array()?))
+    }
+
+    /// Reads the stored checksum, and returns whether it matches everything read so far.
+    pub(crate) fn verify(mut self) -> io::Result<bool> {
+        let expected = self.hasher.finish();
+
+        let mut buf = [0; 8];
+        self.inner.read_exact(&mut buf)?;
+
+        Ok(u64::from_le_bytes(buf) == expected)
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.write(&buf[..read]);
+        Ok(read)
+    }
+}
@@ -0,0 +1,471 @@
+//! `SlidingWindowBloomFilter` only remembers the items that were added to it recently, which
+//! gives "seen in the last N minutes" or "seen in the last N items" semantics.
+//!
+//! The filter is made up of a fixed number of [`BloomFilter`] generations. Items are always added
+//! to the newest generation, and an item is reported as present if any generation might contain
+//! it. The generations are rotated on a schedule: the oldest generation is dropped, and a new,
+//! empty generation takes its place. By default, the filter rotates once the newest generation
+//! holds `items_per_generation` items, but it can also rotate at a fixed time interval, using a
+//! [`Clock`] that can be replaced in tests.
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+//! [`Clock`]: ../clock/trait.Clock.html
+
+use crate::bloom_filter::{split_hash, BloomFilter};
+use crate::clock::{Clock, SystemClock};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+use std::hash::{BuildHasher, Hash};
+use std::time::{Duration, Instant};
+
+/// Represents a sliding window Bloom filter.
+///
+/// When constructing the filter using `new`, you need to specify the desired acceptable
+/// false-positive rate of the whole filter, the number of items each generation should hold, and
+/// the number of generations.
+///
+/// When rotating after a number of items, the filter remembers at least the last
+/// `(num_generations - 1) * items_per_generation` items. When rotating at a time interval set
+/// with `rotate_every`, the filter remembers items for at least `(num_generations - 1) *
+/// interval`, and forgets them after at most `num_generations * interval`.
+///
+/// # Example
+/// ```rust
+/// use flit::clock::ManualClock;
+/// use flit::SlidingWindowBloomFilter;
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let mut filter = SlidingWindowBloomFilter::new(0.01, 10000, 5)
+///     .rotate_every(Duration::from_secs(60))
+///     .with_clock(clock.clone());
+///
+/// filter.add(&"Hello, world!");
+/// assert_eq!(filter.might_contain(&"Hello, world!"), true); // probably true
+///
+/// clock.advance(Duration::from_secs(5 * 60));
+/// assert_eq!(filter.might_contain(&"Hello, world!"), false); // definitely false!
+/// ```
+pub struct SlidingWindowBloomFilter<T, S = XxHashBuilder, C = SystemClock> {
+    generations: VecDeque<BloomFilter<T, S>>,
+    items_per_generation: usize,
+    interval: Option<Duration>,
+    generation_started: Instant,
+    clock: C,
+    build_hasher: S,
+}
+
+impl<T: Hash> SlidingWindowBloomFilter<T> {
+    /// Creates a new sliding window Bloom filter based on the required false positive rate of the
+    /// whole filter, the estimated number of items in each generation, and the number of
+    /// generations.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `items_per_generation` or `num_generations` is not greater than 0, or if the
+    /// generations would be too large to allocate. Use `try_new` to handle these cases without
+    /// panicking.
+    pub fn new(
+        false_positive_rate: f64,
+        items_per_generation: usize,
+        num_generations: usize,
+    ) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            items_per_generation,
+            num_generations,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new sliding window Bloom filter like `new`, but hashes items using the given
+    /// `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `items_per_generation` or `num_generations` is not greater than 0, or if the
+    /// generations would be too large to allocate. Use `try_with_seed` to handle these cases without
+    /// panicking.
+    pub fn with_seed(
+        false_positive_rate: f64,
+        items_per_generation: usize,
+        num_generations: usize,
+        seed: u64,
+    ) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            items_per_generation,
+            num_generations,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Creates a new sliding window Bloom filter like `new`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_new(
+        false_positive_rate: f64,
+        items_per_generation: usize,
+        num_generations: usize,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            items_per_generation,
+            num_generations,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new sliding window Bloom filter like `with_seed`, but returns an error instead
+    /// of panicking if the parameters are invalid.
+    pub fn try_with_seed(
+        false_positive_rate: f64,
+        items_per_generation: usize,
+        num_generations: usize,
+        seed: u64,
+    ) -> Result<Self, FlitError> {
+        Self::try_with_hasher(
+            false_positive_rate,
+            items_per_generation,
+            num_generations,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+}
+
+impl<T, C> SlidingWindowBloomFilter<T, XxHashBuilder, C> {
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone> SlidingWindowBloomFilter<T, S> {
+    /// Creates a new sliding window Bloom filter like `new`, but hashes items using the given
+    /// `build_hasher`. Every generation of the filter uses a copy of `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `items_per_generation` or `num_generations` is not greater than 0, or if the
+    /// generations would be too large to allocate. Use `try_with_hasher` to handle these cases without
+    /// panicking.
+    pub fn with_hasher(
+        false_positive_rate: f64,
+        items_per_generation: usize,
+        num_generations: usize,
+        build_hasher: S,
+    ) -> Self {
+        Self::try_with_hasher(
+            false_positive_rate,
+            items_per_generation,
+            num_generations,
+            build_hasher,
+        )
+        .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new sliding window Bloom filter like `with_hasher`, but returns an error
+    /// instead of panicking if the parameters are invalid.
+    pub fn try_with_hasher(
+        false_positive_rate: f64,
+        items_per_generation: usize,
+        num_generations: usize,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        if num_generations == 0 {
+            return Err(FlitError::InvalidParameter("num_generations"));
+        }
+        if !(false_positive_rate > 0_f64 && false_positive_rate < 1_f64) {
+            return Err(FlitError::InvalidFalsePositiveRate(false_positive_rate));
+        }
+
+        // An item is reported as present if any generation reports it, so each generation needs
+        // a lower false-positive rate than the whole filter.
+        let false_positive_rate =
+            1_f64 - (1_f64 - false_positive_rate).powf(1_f64 / num_generations as f64);
+
+        let generations = (0..num_generations)
+            .map(|_| {
+                BloomFilter::try_with_hasher(
+                    false_positive_rate,
+                    items_per_generation,
+                    build_hasher.clone(),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(SlidingWindowBloomFilter {
+            generations,
+            items_per_generation,
+            interval: None,
+            generation_started: SystemClock.now(),
+            clock: SystemClock,
+            build_hasher,
+        })
+    }
+}
+
+impl<T: Hash, S: BuildHasher + Clone, C: Clock> SlidingWindowBloomFilter<T, S, C> {
+    /// Rotates the generations every `interval`, instead of after every `items_per_generation`
+    /// items.
+    ///
+    /// A generation is not rotated early if more than `items_per_generation` items are added to
+    /// it within a single interval. The filter keeps accepting items, but its false-positive rate
+    /// then rises above the rate it was created with, as reported by `false_positive_rate`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `interval` is zero.
+    pub fn rotate_every(mut self, interval: Duration) -> Self {
+        assert!(
+            interval > Duration::from_secs(0),
+            "Rotation interval must be greater than 0"
+        );
+
+        self.interval = Some(interval);
+        self.generation_started = self.clock.now();
+        self
+    }
+
+    /// Uses `clock` to tell the time, instead of the system clock.
+    ///
+    /// This only has an effect when rotating at a time interval.
+    pub fn with_clock<D: Clock>(self, clock: D) -> SlidingWindowBloomFilter<T, S, D> {
+        SlidingWindowBloomFilter {
+            generations: self.generations,
+            items_per_generation: self.items_per_generation,
+            interval: self.interval,
+            generation_started: clock.now(),
+            clock,
+            build_hasher: self.build_hasher,
+        }
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Adds the `item` to the newest generation of the filter, rotating the generations first if
+    /// they are due.
+    pub fn add(&mut self, item: &T) {
+        match self.interval {
+            Some(interval) => {
+                let intervals = self.elapsed_intervals();
+                for _ in 0..intervals.min(self.generations.len() as u64) {
+                    self.rotate();
+                }
+
+                // Only whole intervals are skipped, so that the newest generation keeps to the
+                // schedule. If that cannot be represented, the schedule restarts from now.
+                self.generation_started = interval
+                    .as_nanos()
+                    .checked_mul(u128::from(intervals))
+                    .and_then(duration_from_nanos)
+                    .and_then(|skipped| self.generation_started.checked_add(skipped))
+                    .unwrap_or_else(|| self.clock.now());
+            }
+            None => {
+                if self.generations[0].len() >= self.items_per_generation as u64 {
+                    self.rotate();
+                }
+            }
+        }
+
+        self.generations[0].add(item);
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the item was either never added to the filter, or it was
+    /// added to a generation that has since expired.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        // Every generation uses the same hasher, so the item only needs to be hashed once.
+        let split_hash = split_hash(item, &self.build_hasher);
+
+        self.live_generations()
+            .any(|generation| generation.contains_split_hash(split_hash))
+    }
+
+    /// Drops the oldest generation, and starts a new, empty generation.
+    pub fn rotate(&mut self) {
+        // The bits of the oldest generation are reused for the new one.
+        self.generations.rotate_right(1);
+        self.generations[0].clear();
+    }
+
+    /// Returns the number of items in the generations that have not expired yet.
+    pub fn len(&self) -> u64 {
+        self.live_generations().map(BloomFilter::len).sum()
+    }
+
+    /// Returns `true` if no items have been added to the generations that have not expired yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of generations in the filter.
+    pub fn num_generations(&self) -> usize {
+        self.generations.len()
+    }
+
+    /// Calculates the current expected false positive rate given the number of items in each
+    /// generation of the filter that has not expired yet.
+    pub fn false_positive_rate(&self) -> f64 {
+        1_f64
+            - self
+                .live_generations()
+                .map(|generation| 1_f64 - generation.false_positive_rate())
+                .product::<f64>()
+    }
+
+    /// Returns the number of whole rotation intervals that have passed since the newest
+    /// generation was started, when rotating at a time interval.
+    fn elapsed_intervals(&self) -> u64 {
+        let interval = match self.interval {
+            Some(interval) => interval,
+            None => return 0,
+        };
+
+        let elapsed = self
+            .clock
+            .now()
+            .saturating_duration_since(self.generation_started);
+
+        u64::try_from(elapsed.as_nanos() / interval.as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    /// Returns the generations that have not expired yet, from newest to oldest.
+    ///
+    /// Generations that are due to be rotated out are skipped, even if `add` has not been called
+    /// since they expired.
+    fn live_generations(&self) -> impl Iterator<Item = &BloomFilter<T, S>> {
+        let expired = self.elapsed_intervals().min(self.generations.len() as u64);
+        let live = self.generations.len() - expired as usize;
+
+        self.generations.iter().take(live)
+    }
+}
+
+/// Converts a number of nanoseconds to a `Duration`, or returns `None` if it is too long.
+fn duration_from_nanos(nanos: u128) -> Option<Duration> {
+    const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).ok()?;
+    Some(Duration::new(secs, (nanos % NANOS_PER_SEC) as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[test]
+    fn test_rotates_after_items() {
+        let mut filter = SlidingWindowBloomFilter::with_seed(0.01_f64, 100, 3, 42);
+
+        for i in 0..300 {
+            filter.add(&i);
+        }
+        assert_eq!(filter.len(), 300);
+        assert!((0..300).all(|i| filter.might_contain(&i)));
+
+        filter.add(&300);
+        assert_eq!(filter.len(), 201);
+        assert!((100..301).all(|i| filter.might_contain(&i)));
+        assert!((0..100).filter(|i| filter.might_contain(i)).count() < 5);
+    }
+
+    #[test]
+    fn test_rotates_at_interval() {
+        let clock = ManualClock::new();
+        let mut filter = SlidingWindowBloomFilter::new(0.01_f64, 100, 3)
+            .rotate_every(Duration::from_secs(60))
+            .with_clock(clock.clone());
+
+        filter.add(&"Hello, world!");
+        clock.advance(Duration::from_secs(119));
+        filter.add(&"Dogs are cool!");
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(filter.might_contain(&"Dogs are cool!"));
+
+        clock.advance(Duration::from_secs(60));
+        assert!(filter.might_contain(&"Hello, world!"));
+
+        // The generation holding "Hello, world!" expires without any further calls to `add`.
+        clock.advance(Duration::from_secs(1));
+        assert!(!filter.might_contain(&"Hello, world!"));
+        assert!(filter.might_contain(&"Dogs are cool!"));
+        assert_eq!(filter.len(), 1);
+
+        clock.advance(Duration::from_secs(3600));
+        assert!(filter.is_empty());
+        assert!(!filter.might_contain(&"Dogs are cool!"));
+
+        filter.add(&"Hello, world!");
+        assert!(filter.might_contain(&"Hello, world!"));
+    }
+
+    #[test]
+    fn test_long_interval() {
+        let clock = ManualClock::new();
+        let interval = Duration::from_secs(1 << 40);
+        let mut filter = SlidingWindowBloomFilter::new(0.01_f64, 100, 3)
+            .rotate_every(interval)
+            .with_clock(clock.clone());
+
+        filter.add(&"Hello, world!");
+        clock.advance(interval * 2);
+        filter.add(&"Dogs are cool!");
+        assert!(filter.might_contain(&"Hello, world!"));
+
+        clock.advance(interval);
+        assert!(!filter.might_contain(&"Hello, world!"));
+        assert!(filter.might_contain(&"Dogs are cool!"));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = SlidingWindowBloomFilter::with_seed(0.01_f64, 10_000, 4, 42);
+
+        for i in 0..40_000 {
+            filter.add(&i);
+        }
+
+        let false_positives = (40_000..140_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+
+        assert!(filter.false_positive_rate() <= 0.0101);
+        assert!(false_positives < 1100);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_no_generations_panics() {
+        SlidingWindowBloomFilter::<u32>::new(0.01_f64, 100, 0);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(matches!(
+            SlidingWindowBloomFilter::<u32>::try_new(0.01_f64, 100, 0),
+            Err(FlitError::InvalidParameter("num_generations"))
+        ));
+        assert!(matches!(
+            SlidingWindowBloomFilter::<u32>::try_new(1.5_f64, 100, 3),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            SlidingWindowBloomFilter::<u32>::try_new(0.01_f64, 0, 3),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(SlidingWindowBloomFilter::<u32>::try_with_seed(0.01_f64, 100, 3, 42).is_ok());
+    }
+}
@@ -0,0 +1,499 @@
+//! `SplitBlockBloomFilter` is the split-block Bloom filter (SBBF) used by
+//! [Apache Parquet](https://parquet.apache.org/) to filter the values of a column chunk.
+//!
+//! The filter is split into 256-bit blocks of eight 32-bit words. Each item is mapped to a single
+//! block, and sets exactly one bit in each of the block's words. The bit in each word is chosen
+//! by multiplying the item's hash with a fixed salt, so that the filter does not depend on
+//! anything other than its bitset.
+//!
+//! Items are hashed with xxHash64 using a seed of 0, over their Parquet `PLAIN` encoding. This
+//! makes the bitsets produced and read by this filter byte-identical to the ones stored in
+//! Parquet files, so a filter can be built for a column chunk, or read from one and queried,
+//! without any other Parquet support.
+//!
+//! # References
+//! - [Parquet Bloom Filter
+//!   specification](https://github.com/apache/parquet-format/blob/master/BloomFilter.md)
+//! - [Cache-, Hash- and Space-Efficient Bloom
+//!   Filters](https://algo2.iti.kit.edu/documents/cacheefficientbloomfilters-jea.pdf)
+
+use crate::error::{DecodeError, FlitError};
+use std::convert::TryFrom;
+use std::hash::Hasher;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use twox_hash::XxHash64;
+
+/// The number of bytes in each block.
+const BLOCK_BYTES: usize = 32;
+
+/// The smallest bitset that Parquet writers produce, which is a single block.
+const MIN_BYTES: usize = BLOCK_BYTES;
+
+/// The largest bitset that Parquet writers produce.
+const MAX_BYTES: usize = 128 * 1024 * 1024;
+
+/// The salts used to pick a bit in each word of a block, as given by the Parquet specification.
+const SALT: [u32; 8] = [
+    0x47b6_137b,
+    0x4497_4d91,
+    0x8824_ad5b,
+    0xa2b7_289d,
+    0x7054_95c7,
+    0x2df1_424b,
+    0x9efc_4947,
+    0x5c6b_fb31,
+];
+
+/// A value that can be stored in a Parquet column, and can therefore be added to a
+/// [`SplitBlockBloomFilter`].
+///
+/// Values are hashed over their Parquet `PLAIN` encoding: integers and floating-point numbers as
+/// their little-endian bytes, and byte arrays and strings as their bytes without a length prefix.
+///
+/// [`SplitBlockBloomFilter`]: struct.SplitBlockBloomFilter.html
+pub trait ParquetValue {
+    /// Returns the xxHash64 (with seed 0) of the `PLAIN` encoding of the value.
+    fn parquet_hash(&self) -> u64;
+}
+
+/// Hashes `bytes` the same way as Parquet does.
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+macro_rules! impl_parquet_value {
+    ($($ty:ty),*) => {
+        $(
+            impl ParquetValue for $ty {
+                fn parquet_hash(&self) -> u64 {
+                    hash_bytes(&self.to_le_bytes())
+                }
+            }
+        )*
+    };
+}
+
+impl_parquet_value!(i32, i64, u32, u64, f32, f64);
+
+impl ParquetValue for [u8] {
+    fn parquet_hash(&self) -> u64 {
+        hash_bytes(self)
+    }
+}
+
+impl ParquetValue for str {
+    fn parquet_hash(&self) -> u64 {
+        hash_bytes(self.as_bytes())
+    }
+}
+
+impl<T: ParquetValue + ?Sized> ParquetValue for &T {
+    fn parquet_hash(&self) -> u64 {
+        (**self).parquet_hash()
+    }
+}
+
+/// Represents a Parquet split-block Bloom filter.
+///
+/// When constructing the filter using `new`, you need to specify the desired acceptable
+/// false-positive rate, and the number of distinct values you intend to store in the filter.
+/// Like Parquet writers, the filter rounds its size up to a power of two bytes, between 32 bytes
+/// and 128 MiB. Use `with_num_bytes` to pick the size of the bitset directly.
+///
+/// Unlike the other filters in this crate, the hasher cannot be changed, since Parquet readers
+/// expect xxHash64 with a seed of 0.
+///
+/// # Example
+/// ```rust
+/// use flit::SplitBlockBloomFilter;
+///
+/// let mut filter = SplitBlockBloomFilter::<str>::new(0.01, 10000);
+/// filter.add("Hello, world!");
+///
+/// assert_eq!(filter.might_contain("Hello, world!"), true); // probably true
+/// assert_eq!(filter.might_contain("Dogs are cool!"), false); // definitely false!
+///
+/// // The bitset can be stored in a Parquet file as-is.
+/// let bytes = filter.to_bytes();
+/// let filter = SplitBlockBloomFilter::<str>::from_bytes(&bytes).unwrap();
+///
+/// assert_eq!(filter.might_contain("Hello, world!"), true);
+/// ```
+pub struct SplitBlockBloomFilter<T: ?Sized> {
+    blocks: Vec<[u32; 8]>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: ParquetValue + ?Sized> SplitBlockBloomFilter<T> {
+    /// Creates a new split-block Bloom filter based on the required false positive rate and the
+    /// estimated number of distinct values that will be added to the filter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// or if `estimated_items` is not greater than 0. Use `try_new` to handle these cases without
+    /// panicking.
+    pub fn new(false_positive_rate: f64, estimated_items: usize) -> Self {
+        Self::try_new(false_positive_rate, estimated_items).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new split-block Bloom filter like `new`, but returns an error instead of
+    /// panicking if the parameters are invalid.
+    pub fn try_new(false_positive_rate: f64, estimated_items: usize) -> Result<Self, FlitError> {
+        if !(false_positive_rate > 0_f64 && false_positive_rate < 1_f64) {
+            return Err(FlitError::InvalidFalsePositiveRate(false_positive_rate));
+        }
+        if estimated_items == 0 {
+            return Err(FlitError::ZeroCapacity);
+        }
+
+        // Each block sets 8 bits per item, so this is the size of a standard Bloom filter with
+        // `k = 8`, as used by Parquet writers.
+        let num_bits = -8_f64 * estimated_items as f64
+            / (1_f64 - false_positive_rate.powf(1_f64 / 8_f64)).ln();
+        let num_bytes = (num_bits / 8_f64).min(MAX_BYTES as f64) as usize;
+
+        Ok(Self::with_num_bytes(num_bytes))
+    }
+
+    /// Creates a new, empty split-block Bloom filter with a bitset of `num_bytes` bytes.
+    ///
+    /// `num_bytes` is rounded up to the next power of two, and clamped to between 32 bytes and
+    /// 128 MiB.
+    pub fn with_num_bytes(num_bytes: usize) -> Self {
+        let num_bytes = num_bytes.clamp(MIN_BYTES, MAX_BYTES).next_power_of_two();
+
+        SplitBlockBloomFilter {
+            blocks: vec![[0; 8]; num_bytes / BLOCK_BYTES],
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Adds the `item` to the filter by setting one bit in each word of its block to `true`.
+    pub fn add(&mut self, item: &T) {
+        self.add_hash(item.parquet_hash());
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the filter definitely does not contain the item.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        self.might_contain_hash(item.parquet_hash())
+    }
+}
+
+impl<T: ?Sized> SplitBlockBloomFilter<T> {
+    /// Adds an item to the filter, given the xxHash64 of its `PLAIN` encoding.
+    ///
+    /// This is useful when the hash has already been computed elsewhere, such as by a Parquet
+    /// writer.
+    pub fn add_hash(&mut self, hash: u64) {
+        let block = self.block_index(hash);
+        let mask = block_mask(hash as u32);
+
+        for (word, mask) in self.blocks[block].iter_mut().zip(mask.iter()) {
+            *word |= mask;
+        }
+    }
+
+    /// Checks if the filter *might* contain an item, given the xxHash64 of its `PLAIN` encoding.
+    pub fn might_contain_hash(&self, hash: u64) -> bool {
+        let block = self.block_index(hash);
+        let mask = block_mask(hash as u32);
+
+        self.blocks[block]
+            .iter()
+            .zip(mask.iter())
+            .all(|(word, mask)| word & mask == *mask)
+    }
+
+    /// Returns the number of bytes in the filter's bitset.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * BLOCK_BYTES
+    }
+
+    /// Returns the fraction of bits in the filter that are set.
+    pub fn fill_ratio(&self) -> f64 {
+        let ones: u64 = self
+            .blocks
+            .iter()
+            .flat_map(|block| block.iter())
+            .map(|word| u64::from(word.count_ones()))
+            .sum();
+
+        ones as f64 / (self.num_bytes() * 8) as f64
+    }
+
+    /// Serializes the filter into its Parquet bitset, which can be turned back into a filter
+    /// using `from_bytes`.
+    ///
+    /// See `write_to` for a description of the format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.num_bytes());
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec should never fail");
+        bytes
+    }
+
+    /// Serializes the filter's bitset into `writer`.
+    ///
+    /// The bitset is written exactly as Parquet stores it after the `BloomFilterHeader`: the
+    /// blocks in order, each as eight 32-bit words in little-endian byte order. There is no
+    /// header or checksum, since Parquet stores those separately.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for block in &self.blocks {
+            for word in block {
+                writer.write_all(&word.to_le_bytes())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Deserializes a filter from a Parquet bitset, such as one produced by `to_bytes`.
+    ///
+    /// Returns an error if `bytes` is empty, or its length is not a multiple of the 32-byte block
+    /// size.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        if bytes.is_empty() || bytes.len() % BLOCK_BYTES != 0 {
+            return Err(DecodeError::InvalidData);
+        }
+        // Block indices are computed from the top 32 bits of the hash.
+        if u32::try_from(bytes.len() / BLOCK_BYTES).is_err() {
+            return Err(DecodeError::InvalidData);
+        }
+
+        let blocks = bytes
+            .chunks_exact(BLOCK_BYTES)
+            .map(|chunk| {
+                let mut block = [0; 8];
+                for (word, bytes) in block.iter_mut().zip(chunk.chunks_exact(4)) {
+                    *word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                }
+                block
+            })
+            .collect();
+
+        Ok(SplitBlockBloomFilter {
+            blocks,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Deserializes a bitset of `num_bytes` bytes from `reader`, such as the one following a
+    /// `BloomFilterHeader` in a Parquet file.
+    ///
+    /// Only the `num_bytes` bytes belonging to the bitset are consumed from `reader`.
+    pub fn read_from<R: Read>(mut reader: R, num_bytes: usize) -> Result<Self, DecodeError> {
+        if num_bytes == 0 || num_bytes % BLOCK_BYTES != 0 {
+            return Err(DecodeError::InvalidData);
+        }
+
+        // Don't trust `num_bytes` with a huge up-front allocation; a truncated input will fail
+        // long before the vector grows that large.
+        let mut bytes = Vec::with_capacity(num_bytes.min(1 << 16));
+        reader
+            .by_ref()
+            .take(num_bytes as u64)
+            .read_to_end(&mut bytes)?;
+        if bytes.len() != num_bytes {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Returns the index of the block for the item with the given `hash`.
+    ///
+    /// Maps the top 32 bits of `hash` onto `0..num_blocks` without a division, as required by the
+    /// Parquet specification.
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+}
+
+impl<T: ?Sized> Clone for SplitBlockBloomFilter<T> {
+    fn clone(&self) -> Self {
+        SplitBlockBloomFilter {
+            blocks: self.blocks.clone(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Returns the bits to set within a block for the lower 32 bits of an item's hash.
+///
+/// Each salt picks one bit out of each of the eight words, using the top 5 bits of the product.
+fn block_mask(hash: u32) -> [u32; 8] {
+    let mut mask = [0; 8];
+    for (mask, salt) in mask.iter_mut().zip(SALT.iter()) {
+        *mask = 1 << (hash.wrapping_mul(*salt) >> 27);
+    }
+
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_num_bytes() {
+        assert_eq!(
+            SplitBlockBloomFilter::<i64>::with_num_bytes(0).num_bytes(),
+            32
+        );
+        assert_eq!(
+            SplitBlockBloomFilter::<i64>::with_num_bytes(33).num_bytes(),
+            64
+        );
+        assert_eq!(
+            SplitBlockBloomFilter::<i64>::with_num_bytes(usize::MAX).num_bytes(),
+            MAX_BYTES
+        );
+
+        // About 1.2 MB, rounded up to the next power of two.
+        let filter = SplitBlockBloomFilter::<i64>::new(0.01_f64, 1_000_000);
+        assert_eq!(filter.num_bytes(), 2 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(matches!(
+            SplitBlockBloomFilter::<i64>::try_new(0_f64, 100),
+            Err(FlitError::InvalidFalsePositiveRate(_))
+        ));
+        assert!(matches!(
+            SplitBlockBloomFilter::<i64>::try_new(0.01_f64, 0),
+            Err(FlitError::ZeroCapacity)
+        ));
+    }
+
+    #[test]
+    fn test_block_mask() {
+        for hash in (0..u32::MAX).step_by(65_537) {
+            assert!(block_mask(hash).iter().all(|word| word.count_ones() == 1));
+        }
+    }
+
+    #[test]
+    fn test_parquet_hash() {
+        // Reference values from the xxHash64 test vectors.
+        assert_eq!("".parquet_hash(), 0xef46_db37_51d8_e999);
+        assert_eq!("abc".parquet_hash(), 0x44bc_2cf5_ad77_0999);
+        assert_eq!(7_i64.parquet_hash(), hash_bytes(&[7, 0, 0, 0, 0, 0, 0, 0]));
+    }
+
+    /// Inserts `value` into `bitset` by following the Parquet specification literally, word by
+    /// word on the little-endian bytes, without using any of the filter's code.
+    fn spec_insert(bitset: &mut [u8], value: &[u8]) {
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(value);
+        let hash = hasher.finish();
+
+        let z = (bitset.len() / 32) as u64;
+        let block = (((hash >> 32) * z) >> 32) as usize;
+        let key = hash as u32;
+
+        for (i, salt) in SALT.iter().enumerate() {
+            let offset = block * 32 + i * 4;
+            let mut word =
+                u32::from_le_bytes(<[u8; 4]>::try_from(&bitset[offset..offset + 4]).unwrap());
+            word |= 1 << (key.wrapping_mul(*salt) >> 27);
+            bitset[offset..offset + 4].copy_from_slice(&word.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn test_parquet_fixture() {
+        // The bitset of a string column holding the values "a0" to "a9", written by parquet-mr
+        // through Spark. It is the fixture used by `test_with_fixture` in the `parquet` crate
+        // (https://github.com/apache/arrow-rs, parquet/src/bloom_filter/mod.rs). It is also
+        // regenerated below from a literal transcription of the specification, so that the test
+        // does not depend on that origin alone.
+        let bytes = [
+            200, 1, 80, 20, 64, 68, 8, 109, 6, 37, 4, 67, 144, 80, 96, 32, 8, 132, 43, 33, 0, 5,
+            99, 65, 2, 0, 224, 44, 64, 78, 96, 4,
+        ];
+
+        let filter = SplitBlockBloomFilter::<str>::from_bytes(&bytes).unwrap();
+        for i in 0..10 {
+            assert!(filter.might_contain(&format!("a{}", i)));
+        }
+
+        let mut rebuilt = SplitBlockBloomFilter::<str>::with_num_bytes(bytes.len());
+        for i in 0..10 {
+            rebuilt.add(&format!("a{}", i));
+        }
+        assert_eq!(rebuilt.to_bytes(), bytes);
+
+        let mut reference = [0_u8; 32];
+        for i in 0..10 {
+            spec_insert(&mut reference, format!("a{}", i).as_bytes());
+        }
+        assert_eq!(reference, bytes);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut filter = SplitBlockBloomFilter::<str>::new(0.03_f64, 10);
+
+        filter.add("Hello, world!");
+
+        assert!(filter.fill_ratio() > 0.0);
+        assert!(filter.might_contain("Hello, world!"));
+        assert!(!filter.might_contain("Dogs are cool!"));
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = SplitBlockBloomFilter::<i64>::new(0.01_f64, 100_000);
+
+        for i in 0..100_000 {
+            filter.add(&i);
+        }
+
+        let false_positives = (100_000..200_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+
+        assert!((0..100_000).all(|i| filter.might_contain(&i)));
+        assert!(false_positives < 1100);
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() {
+        let mut filter = SplitBlockBloomFilter::<i32>::with_num_bytes(1024);
+        for i in 0..100 {
+            filter.add(&i);
+        }
+
+        let bytes = filter.to_bytes();
+        assert_eq!(bytes.len(), 1024);
+
+        let decoded = SplitBlockBloomFilter::<i32>::read_from(&bytes[..], 1024).unwrap();
+        assert_eq!(decoded.to_bytes(), bytes);
+        assert!((0..100).all(|i| decoded.might_contain(&i)));
+    }
+
+    #[test]
+    fn test_from_bytes_invalid_length() {
+        assert!(matches!(
+            SplitBlockBloomFilter::<i32>::from_bytes(&[]),
+            Err(DecodeError::InvalidData)
+        ));
+        assert!(matches!(
+            SplitBlockBloomFilter::<i32>::from_bytes(&[0; 33]),
+            Err(DecodeError::InvalidData)
+        ));
+        assert!(matches!(
+            SplitBlockBloomFilter::<i32>::read_from(&[0_u8; 16][..], 32),
+            Err(DecodeError::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,417 @@
+//! `StableBloomFilter` is a variant of a Bloom filter for unbounded streams of items. A standard
+//! [`BloomFilter`] eventually fills up with ones when items keep being added to it, at which
+//! point it reports every item as present. A Stable Bloom filter continuously evicts old
+//! information instead, so that its false-positive rate converges to a fixed bound, no matter how
+//! many items are added.
+//!
+//! Each cell of the filter is a small counter. Adding an item first decrements `P` cells chosen
+//! at random, and then sets the item's `k` cells to their maximum value. An item is reported as
+//! present if all of its cells are non-zero. Items that have not been added for a while are
+//! eventually decremented out of the filter, so unlike a `BloomFilter`, the filter has false
+//! negatives as well as false positives. Items that were added recently are the least likely to
+//! have been evicted, which makes the filter suitable for approximate "have I seen this recently"
+//! checks, such as deduplicating a stream.
+//!
+//! # References
+//! - [Approximately Detecting Duplicates for Streaming Data using Stable Bloom
+//!   Filters](https://webdocs.cs.ualberta.ca/~drafiei/papers/DupDet06Sigmod.pdf)
+//!
+//! [`BloomFilter`]: ../bloom_filter/struct.BloomFilter.html
+
+use crate::bloom_filter::{indices_for_hash, split_hash};
+use crate::error::FlitError;
+use crate::hash::XxHashBuilder;
+use crate::packed_vec::PackedVec;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+
+/// The default number of bits in each cell.
+const DEFAULT_CELL_BITS: u32 = 1;
+
+/// The largest number of bits that can be used for each cell.
+const MAX_CELL_BITS: u32 = 8;
+
+/// Represents a Stable Bloom filter.
+///
+/// When constructing the filter using `new`, you need to specify the desired false-positive rate
+/// that the filter converges to, and the number of cells in the filter. More cells give the same
+/// false-positive rate with fewer false negatives, since each item takes longer to be evicted.
+///
+/// # Example
+/// ```rust
+/// use flit::StableBloomFilter;
+///
+/// let mut filter = StableBloomFilter::new(0.01, 100_000);
+///
+/// assert_eq!(filter.check_and_add(&"Hello, world!"), false); // definitely not seen before
+/// assert_eq!(filter.check_and_add(&"Hello, world!"), true); // probably seen before
+/// ```
+pub struct StableBloomFilter<T, S = XxHashBuilder> {
+    m: u64,
+    k: u32,
+    p: u64,
+    cells: PackedVec,
+    rng: StdRng,
+    build_hasher: S,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Hash> StableBloomFilter<T> {
+    /// Creates a new Stable Bloom filter with 1-bit cells, based on the false positive rate that
+    /// the filter should converge to and the number of cells in the filter.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `num_cells` is not greater than the number of hashes needed for the false positive
+    /// rate, or if the filter would be too large to allocate.
+    pub fn new(false_positive_rate: f64, num_cells: usize) -> Self {
+        Self::with_cell_bits(false_positive_rate, num_cells, DEFAULT_CELL_BITS)
+    }
+
+    /// Creates a new Stable Bloom filter whose cells are `cell_bits` bits wide.
+    ///
+    /// Wider cells take longer to be decremented back to zero, which lowers the rate of false
+    /// negatives for the same false positive rate and number of cells, but take up more space.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `num_cells` is not greater than the number of hashes needed for the false positive
+    /// rate, if `cell_bits` is not between 1 and 8, or if the filter would be too large to
+    /// allocate.
+    pub fn with_cell_bits(false_positive_rate: f64, num_cells: usize, cell_bits: u32) -> Self {
+        Self::with_cell_bits_and_hasher(
+            false_positive_rate,
+            num_cells,
+            cell_bits,
+            XxHashBuilder::random(),
+        )
+    }
+
+    /// Creates a new Stable Bloom filter with 1-bit cells, which hashes items using the given
+    /// `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `num_cells` is not greater than the number of hashes needed for the false positive
+    /// rate, or if the filter would be too large to allocate.
+    pub fn with_seed(false_positive_rate: f64, num_cells: usize, seed: u64) -> Self {
+        Self::with_hasher(
+            false_positive_rate,
+            num_cells,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Creates a new Stable Bloom filter whose cells are `cell_bits` bits wide, which hashes
+    /// items using the given `seed`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `num_cells` is not greater than the number of hashes needed for the false positive
+    /// rate, if `cell_bits` is not between 1 and 8, or if the filter would be too large to
+    /// allocate.
+    pub fn with_cell_bits_and_seed(
+        false_positive_rate: f64,
+        num_cells: usize,
+        cell_bits: u32,
+        seed: u64,
+    ) -> Self {
+        Self::with_cell_bits_and_hasher(
+            false_positive_rate,
+            num_cells,
+            cell_bits,
+            XxHashBuilder::with_seed(seed),
+        )
+    }
+
+    /// Returns the seed used to hash items added to the filter.
+    pub fn seed(&self) -> u64 {
+        self.build_hasher.seed()
+    }
+}
+
+impl<T: Hash, S: BuildHasher> StableBloomFilter<T, S> {
+    /// Creates a new Stable Bloom filter with 1-bit cells, which hashes items using the given
+    /// `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `num_cells` is not greater than the number of hashes needed for the false positive
+    /// rate, or if the filter would be too large to allocate.
+    pub fn with_hasher(false_positive_rate: f64, num_cells: usize, build_hasher: S) -> Self {
+        Self::with_cell_bits_and_hasher(
+            false_positive_rate,
+            num_cells,
+            DEFAULT_CELL_BITS,
+            build_hasher,
+        )
+    }
+
+    /// Creates a new Stable Bloom filter whose cells are `cell_bits` bits wide, which hashes
+    /// items using the given `build_hasher`.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if `false_positive_rate` is not between 0 and 1 (non inclusive),
+    /// if `num_cells` is not greater than the number of hashes needed for the false positive
+    /// rate, if `cell_bits` is not between 1 and 8, or if the filter would be too large to
+    /// allocate. Use `try_with_cell_bits_and_hasher` to handle these cases without panicking.
+    pub fn with_cell_bits_and_hasher(
+        false_positive_rate: f64,
+        num_cells: usize,
+        cell_bits: u32,
+        build_hasher: S,
+    ) -> Self {
+        Self::try_with_cell_bits_and_hasher(false_positive_rate, num_cells, cell_bits, build_hasher)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Creates a new Stable Bloom filter like `with_cell_bits_and_hasher`, but returns an error
+    /// instead of panicking if the parameters are invalid.
+    pub fn try_with_cell_bits_and_hasher(
+        false_positive_rate: f64,
+        num_cells: usize,
+        cell_bits: u32,
+        build_hasher: S,
+    ) -> Result<Self, FlitError> {
+        if !(false_positive_rate > 0_f64 && false_positive_rate < 1_f64) {
+            return Err(FlitError::InvalidFalsePositiveRate(false_positive_rate));
+        }
+        if num_cells == 0 {
+            return Err(FlitError::ZeroCapacity);
+        }
+        if cell_bits == 0 || cell_bits > MAX_CELL_BITS {
+            return Err(FlitError::InvalidParameter("cell_bits"));
+        }
+        if num_cells.checked_mul(cell_bits as usize).is_none() {
+            return Err(FlitError::CapacityOverflow);
+        }
+
+        let num_hashes = ((1_f64 / false_positive_rate).log2().ceil() as u32).max(1);
+        if num_cells as u64 <= u64::from(num_hashes) {
+            return Err(FlitError::InvalidParameter("num_cells"));
+        }
+
+        let max = f64::from((1_u32 << cell_bits) - 1);
+        let decrements = optimal_decrements(false_positive_rate, num_cells as u64, num_hashes, max);
+
+        // The cells to decrement are picked using the hasher, so that a filter created with a fixed
+        // seed behaves the same every time.
+        let rng = StdRng::seed_from_u64(build_hasher.hash_one(num_cells as u64));
+
+        Ok(StableBloomFilter {
+            m: num_cells as u64,
+            k: num_hashes,
+            p: decrements,
+            cells: PackedVec::new(cell_bits, num_cells),
+            rng,
+            build_hasher,
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Returns a reference to the filter's `BuildHasher`.
+    pub fn hasher(&self) -> &S {
+        &self.build_hasher
+    }
+
+    /// Adds the `item` to the filter, first decrementing `P` random cells to evict old items.
+    ///
+    /// The random cells are chosen by a generator seeded from the filter's hasher, so filters
+    /// created with the same seed evict the same cells.
+    pub fn add(&mut self, item: &T) {
+        self.decrement_random_cells();
+
+        let max = self.cells.max_value();
+        for i in indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k) {
+            self.cells.set(i, max);
+        }
+    }
+
+    /// Checks if the filter *might* contain the `item`.
+    ///
+    /// If this function returns false, the item has either never been added to the filter, or
+    /// has been evicted since it was added.
+    /// If this function returns true, the filter *might* contain the item, but it might also be a
+    /// false-positive.
+    pub fn might_contain(&self, item: &T) -> bool {
+        indices_for_hash(split_hash(item, &self.build_hasher), self.m, self.k)
+            .all(|i| self.cells.get(i) != 0)
+    }
+
+    /// Checks if the filter *might* contain the `item`, and then adds it.
+    ///
+    /// This is equivalent to calling `might_contain` followed by `add`, but only hashes the item
+    /// once.
+    pub fn check_and_add(&mut self, item: &T) -> bool {
+        let split = split_hash(item, &self.build_hasher);
+        let present = indices_for_hash(split, self.m, self.k).all(|i| self.cells.get(i) != 0);
+
+        self.decrement_random_cells();
+
+        let max = self.cells.max_value();
+        for i in indices_for_hash(split, self.m, self.k) {
+            self.cells.set(i, max);
+        }
+
+        present
+    }
+
+    /// Decrements `P` consecutive cells, starting from a random cell.
+    ///
+    /// Decrementing consecutive cells rather than independently chosen ones only needs a single
+    /// random number, and has the same effect on the false positive rate.
+    fn decrement_random_cells(&mut self) {
+        let start = self.rng.gen_range(0, self.m);
+
+        for offset in 0..self.p {
+            let i = ((start + offset) % self.m) as usize;
+            let count = self.cells.get(i);
+            if count > 0 {
+                self.cells.set(i, count - 1);
+            }
+        }
+    }
+}
+
+impl<T, S> StableBloomFilter<T, S> {
+    /// Returns the number of cells in the filter.
+    pub fn num_cells(&self) -> u64 {
+        self.m
+    }
+
+    /// Returns the number of cells set for each item.
+    pub fn num_hashes(&self) -> u32 {
+        self.k
+    }
+
+    /// Returns the number of cells that are decremented every time an item is added (`P`).
+    pub fn num_decrements(&self) -> u64 {
+        self.p
+    }
+
+    /// Returns the fraction of cells in the filter that are non-zero.
+    ///
+    /// This converges to a fixed value as items are added to the filter.
+    pub fn fill_ratio(&self) -> f64 {
+        let non_zero = (0..self.cells.len())
+            .filter(|&i| self.cells.get(i) != 0)
+            .count();
+
+        non_zero as f64 / self.m as f64
+    }
+
+    /// Estimates the current false positive rate of the filter, based on the fraction of cells
+    /// that are non-zero.
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.k as i32)
+    }
+
+    /// Calculates the false positive rate that the filter converges to as items are added to it.
+    pub fn stable_false_positive_rate(&self) -> f64 {
+        let max = self.cells.max_value() as f64;
+        let decay = 1_f64 / (self.p as f64 * (1_f64 / f64::from(self.k) - 1_f64 / self.m as f64));
+        let stable_zeros = (1_f64 / (1_f64 + decay)).powf(max);
+
+        (1_f64 - stable_zeros).powi(self.k as i32)
+    }
+}
+
+/// Calculates the number of cells to decrement for each added item (`P`), so that a filter with
+/// `m` cells, `k` hashes and a maximum cell value of `max` converges to `false_positive_rate`.
+///
+/// Rounding up means that the filter converges to a slightly lower false positive rate.
+fn optimal_decrements(false_positive_rate: f64, m: u64, k: u32, max: f64) -> u64 {
+    let stable_zeros = 1_f64 - false_positive_rate.powf(1_f64 / f64::from(k));
+    let denominator = (1_f64 / stable_zeros.powf(1_f64 / max) - 1_f64)
+        * (1_f64 / f64::from(k) - 1_f64 / m as f64);
+
+    ((1_f64 / denominator).ceil() as u64).clamp(1, m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameters() {
+        let filter = StableBloomFilter::<u32>::new(0.01_f64, 100_000);
+
+        assert_eq!(filter.num_cells(), 100_000);
+        assert_eq!(filter.num_hashes(), 7);
+        assert_eq!(filter.num_decrements(), 7);
+        assert!(filter.stable_false_positive_rate() <= 0.01);
+        assert!(filter.stable_false_positive_rate() > 0.005);
+
+        let filter = StableBloomFilter::<u32>::with_cell_bits(0.01_f64, 100_000, 3);
+        assert!(filter.stable_false_positive_rate() <= 0.01);
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let build_hasher = XxHashBuilder::with_seed(42);
+
+        assert!(matches!(
+            StableBloomFilter::<u32>::try_with_cell_bits_and_hasher(0.01, 0, 1, build_hasher),
+            Err(FlitError::ZeroCapacity)
+        ));
+        assert!(matches!(
+            StableBloomFilter::<u32>::try_with_cell_bits_and_hasher(0.01, 100, 0, build_hasher),
+            Err(FlitError::InvalidParameter("cell_bits"))
+        ));
+        assert!(matches!(
+            StableBloomFilter::<u32>::try_with_cell_bits_and_hasher(0.01, 7, 1, build_hasher),
+            Err(FlitError::InvalidParameter("num_cells"))
+        ));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut filter = StableBloomFilter::new(0.01_f64, 1000);
+
+        filter.add(&"Hello, world!");
+
+        assert!(filter.might_contain(&"Hello, world!"));
+        assert!(!filter.might_contain(&"Dogs are cool!"));
+        assert!(!filter.check_and_add(&"Dogs are cool!"));
+        assert!(filter.check_and_add(&"Dogs are cool!"));
+    }
+
+    #[test]
+    fn test_seeded_filters_evict_the_same_cells() {
+        let mut a = StableBloomFilter::with_seed(0.01_f64, 1000, 42);
+        let mut b = StableBloomFilter::with_seed(0.01_f64, 1000, 42);
+
+        for i in 0..10_000 {
+            a.add(&i);
+            b.add(&i);
+        }
+        assert!((0..10_000).all(|i| a.check_and_add(&i) == b.check_and_add(&i)));
+
+        assert_eq!(a.cells, b.cells);
+    }
+
+    #[test]
+    fn test_false_positive_rate_is_stable() {
+        let mut filter = StableBloomFilter::with_cell_bits_and_seed(0.01_f64, 100_000, 2, 42);
+
+        for i in 0..1_000_000 {
+            filter.add(&i);
+        }
+
+        let false_positives = (1_000_000..1_100_000)
+            .filter(|i| filter.might_contain(i))
+            .count();
+
+        assert!(filter.fill_ratio() < 0.6);
+        assert!(false_positives < 1500);
+        assert!((999_900..1_000_000).all(|i| filter.might_contain(&i)));
+    }
+}